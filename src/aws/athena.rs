@@ -1,20 +1,85 @@
-use crate::aws::config::build_config;
-use crate::aws::error::{Result, ShellError};
+use crate::aws::error::{Result, ShellError, athena_error};
+use crate::aws::request_log::RequestLog;
 use aws_sdk_athena::Client as AthenaClient;
-use aws_sdk_athena::types::{QueryExecutionContext, ResultConfiguration};
+use aws_sdk_athena::types::{
+    QueryExecutionContext, QueryRuntimeStatistics, ResultConfiguration,
+    ResultReuseByAgeConfiguration, ResultReuseConfiguration,
+};
 
-const RETRY_MAX_ATTEMPTS: i32 = 5;
+pub const DEFAULT_CATALOG: &str = "AwsDataCatalog";
+
+#[derive(Clone)]
+pub struct QueryContext {
+    pub database: String,
+    pub workgroup: String,
+    pub output_bucket: Option<String>,
+    pub result_reuse_max_age: Option<i32>, // minutes; None disables result reuse
+}
+
+#[derive(Clone)]
+pub struct QuerySummary {
+    pub execution_id: String,
+    pub state: String,
+    pub state_change_reason: Option<String>,
+    pub error_category: Option<i32>, // 1 system, 2 user, 3 other
+    pub error_type: Option<i32>,
+    pub retryable: Option<bool>, // whether Athena expects a retry of the query to succeed
+    pub data_scanned_bytes: Option<i64>,
+    pub execution_time_ms: Option<i64>,
+    pub reused_previous_result: bool,
+}
+
+impl QuerySummary {
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state.as_str(), "SUCCEEDED" | "FAILED" | "CANCELLED")
+    }
+
+    // A failure Athena expects a resubmission to overcome, such as S3 slowing down or an internal error
+    pub fn is_transient_failure(&self) -> bool {
+        self.state == "FAILED" && self.retryable == Some(true)
+    }
+
+    // Why the query did not succeed, with Athena's error category and type when it gave them
+    pub fn failure_reason(&self) -> Option<String> {
+        let reason = self.state_change_reason.clone()?;
+        let category = match self.error_category {
+            Some(1) => "system error",
+            Some(2) => "user error",
+            Some(_) => "error",
+            None => return Some(reason),
+        };
+        let mut details = match self.error_type {
+            Some(error_type) => format!("{} {}", category, error_type),
+            None => category.to_string(),
+        };
+        match self.retryable {
+            Some(true) => details.push_str(", retryable"),
+            Some(false) => details.push_str(", not retryable"),
+            None => {}
+        }
+        Some(format!("{} [{}]", reason, details))
+    }
+}
 
 pub struct AthenaService(AthenaClient);
 
 impl AthenaService {
     pub async fn new(config: &aws_types::SdkConfig) -> Result<Self> {
-        let client = AthenaClient::new(&config);
+        let athena_config = aws_sdk_athena::config::Builder::from(config)
+            .interceptor(RequestLog)
+            .build();
+        let client = AthenaClient::from_conf(athena_config);
         Ok(AthenaService(client))
     }
 
     pub async fn list_catalogs(&self) -> Result<Vec<String>> {
-        let mut catalogs: Vec<String> = Vec::new();
+        let catalogs = self.list_catalogs_with_types().await?;
+        Ok(catalogs.into_iter().map(|(name, _)| name).collect())
+    }
+
+    // Catalogs with their type, e.g. GLUE, LAMBDA or HIVE
+    pub async fn list_catalogs_with_types(&self) -> Result<Vec<(String, String)>> {
+        let mut catalogs: Vec<(String, String)> = Vec::new();
         let mut next_token: Option<String> = None;
 
         loop {
@@ -23,14 +88,12 @@ impl AthenaService {
                 request = request.next_token(token);
             }
 
-            let response = request.send().await.map_err(|e| {
-                eprintln!("AWS Error Details: {:?}", e);
-                ShellError::AthenaSdkGenericError(e.into())
-            })?;
+            let response = request.send().await.map_err(athena_error)?;
 
             for summary in response.data_catalogs_summary() {
                 if let Some(name) = summary.catalog_name() {
-                    catalogs.push(name.into())
+                    let catalog_type = summary.r#type().map(|t| t.as_str()).unwrap_or_default();
+                    catalogs.push((name.into(), catalog_type.into()))
                 }
             }
 
@@ -43,7 +106,15 @@ impl AthenaService {
     }
 
     pub async fn list_databases(&self, catalog_name: &str) -> Result<Vec<String>> {
-        let mut databases: Vec<String> = Vec::new();
+        let databases = self.list_databases_with_descriptions(catalog_name).await?;
+        Ok(databases.into_iter().map(|(name, _)| name).collect())
+    }
+
+    pub async fn list_databases_with_descriptions(
+        &self,
+        catalog_name: &str,
+    ) -> Result<Vec<(String, Option<String>)>> {
+        let mut databases: Vec<(String, Option<String>)> = Vec::new();
         let mut response = self
             .0
             .list_databases()
@@ -51,74 +122,184 @@ impl AthenaService {
             .into_paginator()
             .send();
         while let Some(stream) = response.next().await {
-            let x = stream.map_err(|e| {
-                eprintln!("AWS Error Details: {:?}", e);
-                ShellError::AthenaSdkGenericError(e.into())
-            })?;
+            let x = stream.map_err(athena_error)?;
             for db in x.database_list() {
-                let name = db.name();
-                databases.push(name.into());
+                databases.push((db.name().into(), db.description().map(String::from)));
             }
         }
         Ok(databases)
     }
 
+    // Athena starts a statement once per client request token, so a resubmission with the same
+    // token returns the execution the first submission started
     pub async fn invoke_query(
         &self,
-        db_name: &str,
+        ctx: &QueryContext,
         query: &str,
-        output_bucket: &str,
+        params: &[String],
+        client_request_token: &str,
     ) -> Result<String> {
-        let response = self
+        let mut request = self
             .0
             .start_query_execution()
-            .query_execution_context(QueryExecutionContext::builder().database(db_name).build())
-            .query_string(query)
-            .result_configuration(
+            .client_request_token(client_request_token)
+            .query_execution_context(
+                QueryExecutionContext::builder()
+                    .database(&ctx.database)
+                    .build(),
+            )
+            .work_group(&ctx.workgroup)
+            .query_string(query);
+        // Athena rejects an empty parameter list, so only send it when there are values to bind
+        if !params.is_empty() {
+            request = request.set_execution_parameters(Some(params.to_vec()));
+        }
+        if let Some(max_age) = ctx.result_reuse_max_age {
+            request = request.result_reuse_configuration(
+                ResultReuseConfiguration::builder()
+                    .result_reuse_by_age_configuration(
+                        ResultReuseByAgeConfiguration::builder()
+                            .enabled(true)
+                            .max_age_in_minutes(max_age)
+                            .build(),
+                    )
+                    .build(),
+            );
+        }
+        if let Some(output_bucket) = &ctx.output_bucket {
+            request = request.result_configuration(
                 ResultConfiguration::builder()
                     .output_location(format!("s3://{output_bucket}/"))
                     .build(),
-            )
-            .send()
-            .await
-            .map_err(|e| {
-                eprintln!("AWS Error Details: {:?}", e);
-                ShellError::AthenaSdkGenericError(e.into())
-            })?;
+            );
+        }
+        let response = request.send().await.map_err(athena_error)?;
         let query_execution_id = response
             .query_execution_id()
             .ok_or_else(|| ShellError::MissingData)?;
         Ok(query_execution_id.to_string())
     }
 
-    pub async fn has_query_succeeded(&self, execution_id: &str, timeout: u64) -> Result<bool> {
-        for _ in 0..RETRY_MAX_ATTEMPTS {
-            let response = self
-                .0
-                .get_query_execution()
-                .query_execution_id(execution_id)
-                .send()
-                .await
-                .map_err(|e| {
-                    eprintln!("AWS Error Details: {:?}", e);
-                    ShellError::AthenaSdkGenericError(e.into())
-                })?;
-
-            if let Some(id) = response.query_execution() {
-                let state = id.status().and_then(|s| s.state());
-                if let Some(s) = state {
-                    match s.as_str() {
-                        "SUCCEEDED" => return Ok(true),
-                        "FAILED" | "CANCELLED" => return Ok(false),
-                        "RUNNING" | "QUEUED" => {
-                            tokio::time::sleep(tokio::time::Duration::from_secs(timeout)).await;
-                        }
-                        _ => return Ok(false),
-                    }
+    pub async fn create_prepared_statement(
+        &self,
+        workgroup: &str,
+        name: &str,
+        query: &str,
+    ) -> Result<()> {
+        self.0
+            .create_prepared_statement()
+            .work_group(workgroup)
+            .statement_name(name)
+            .query_statement(query)
+            .send()
+            .await
+            .map_err(athena_error)?;
+        Ok(())
+    }
+
+    pub async fn update_prepared_statement(
+        &self,
+        workgroup: &str,
+        name: &str,
+        query: &str,
+    ) -> Result<()> {
+        self.0
+            .update_prepared_statement()
+            .work_group(workgroup)
+            .statement_name(name)
+            .query_statement(query)
+            .send()
+            .await
+            .map_err(athena_error)?;
+        Ok(())
+    }
+
+    pub async fn delete_prepared_statement(&self, workgroup: &str, name: &str) -> Result<()> {
+        self.0
+            .delete_prepared_statement()
+            .work_group(workgroup)
+            .statement_name(name)
+            .send()
+            .await
+            .map_err(athena_error)?;
+        Ok(())
+    }
+
+    pub async fn list_prepared_statements(&self, workgroup: &str) -> Result<Vec<String>> {
+        let mut statements: Vec<String> = Vec::new();
+        let mut response = self
+            .0
+            .list_prepared_statements()
+            .work_group(workgroup)
+            .into_paginator()
+            .send();
+        while let Some(stream) = response.next().await {
+            let x = stream.map_err(athena_error)?;
+            for statement in x.prepared_statements() {
+                if let Some(name) = statement.statement_name() {
+                    statements.push(name.into());
                 }
             }
         }
-        Ok(false)
+        Ok(statements)
+    }
+
+    pub async fn get_query_summary(&self, execution_id: &str) -> Result<QuerySummary> {
+        let response = self
+            .0
+            .get_query_execution()
+            .query_execution_id(execution_id)
+            .send()
+            .await
+            .map_err(athena_error)?;
+        let execution = response
+            .query_execution()
+            .ok_or_else(|| ShellError::MissingData)?;
+        let status = execution.status().ok_or_else(|| ShellError::MissingData)?;
+        let statistics = execution.statistics();
+        Ok(QuerySummary {
+            execution_id: execution_id.to_string(),
+            state: status
+                .state()
+                .map(|s| s.as_str().to_string())
+                .ok_or_else(|| ShellError::MissingData)?,
+            state_change_reason: status.state_change_reason().map(|r| r.to_string()),
+            error_category: status.athena_error().and_then(|e| e.error_category()),
+            error_type: status.athena_error().and_then(|e| e.error_type()),
+            retryable: status.athena_error().map(|e| e.retryable()),
+            data_scanned_bytes: statistics.and_then(|s| s.data_scanned_in_bytes()),
+            execution_time_ms: statistics.and_then(|s| s.total_execution_time_in_millis()),
+            reused_previous_result: statistics
+                .and_then(|s| s.result_reuse_information())
+                .is_some_and(|r| r.reused_previous_result()),
+        })
+    }
+
+    pub async fn get_query_runtime_statistics(
+        &self,
+        execution_id: &str,
+    ) -> Result<QueryRuntimeStatistics> {
+        let response = self
+            .0
+            .get_query_runtime_statistics()
+            .query_execution_id(execution_id)
+            .send()
+            .await
+            .map_err(athena_error)?;
+        response
+            .query_runtime_statistics()
+            .cloned()
+            .ok_or_else(|| ShellError::MissingData)
+    }
+
+    pub async fn stop_query(&self, execution_id: &str) -> Result<()> {
+        self.0
+            .stop_query_execution()
+            .query_execution_id(execution_id)
+            .send()
+            .await
+            .map_err(athena_error)?;
+        Ok(())
     }
 
     pub async fn get_query_results(&self, execution_id: &str) -> Result<Vec<Vec<String>>> {
@@ -131,10 +312,7 @@ impl AthenaService {
             .send();
 
         while let Some(stream) = result.next().await {
-            let x = stream.map_err(|e| {
-                eprintln!("AWS Error Details: {:?}", e);
-                ShellError::AthenaSdkGenericError(e.into())
-            })?;
+            let x = stream.map_err(athena_error)?;
             if let Some(rs) = x.result_set() {
                 rs.rows().iter().for_each(|row| {
                     let mut row_data: Vec<String> = Vec::new();
@@ -0,0 +1,35 @@
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+// The file caching key under dir in the user's cache directory, with the key reduced to
+// characters that are safe in a file name
+pub fn cache_path(dir: &str, key: &str) -> Option<PathBuf> {
+    let key = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    let base = directories::BaseDirs::new()?;
+    Some(base.cache_dir().join(dir).join(format!("{}.json", key)))
+}
+
+// Write a cache file readable only by the current user. Failures are only logged, since
+// they just cost redoing the cached work next time.
+pub fn save(path: &Path, contents: &str) {
+    let result = path
+        .parent()
+        .map(std::fs::create_dir_all)
+        .unwrap_or(Ok(()))
+        .and_then(|_| write_private(path, contents.as_bytes()));
+    if let Err(e) = result {
+        warn!(path = %path.display(), error = %e, "could not write the cache");
+    }
+}
+
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
@@ -0,0 +1,368 @@
+use crate::aws::error::{Result, ShellError};
+use aws_smithy_http_client::tls::{self, rustls_provider::CryptoMode};
+use aws_smithy_runtime_api::client::http::{
+    HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpClient,
+    SharedHttpConnector,
+};
+use aws_smithy_runtime_api::client::orchestrator::{HttpRequest, HttpResponse};
+use aws_smithy_runtime_api::client::result::ConnectorError;
+use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+use aws_smithy_runtime_api::http::{Headers, StatusCode};
+use aws_smithy_types::base64;
+use aws_smithy_types::body::SdkBody;
+use aws_smithy_types::byte_stream::ByteStream;
+use serde_json::{Map, Value, json};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const CASSETTE_VERSION: u64 = 2;
+const SCRUBBED: &str = "<scrubbed>";
+const SCRUBBED_HEADERS: [&str; 2] = ["authorization", "x-amz-security-token"];
+// Request parameters and response fields that carry credentials or MFA codes
+const SCRUBBED_FIELDS: [&str; 4] = [
+    "AccessKeyId",
+    "SecretAccessKey",
+    "SessionToken",
+    "TokenCode",
+];
+
+// How the AWS clients of a session reach AWS: directly, recording every request and response
+// into a cassette file, or answering from a recorded cassette without touching the network
+#[derive(Clone, Debug, Default)]
+pub enum Traffic {
+    #[default]
+    Live,
+    Record(Arc<Cassette>),
+    Replay(Arc<Cassette>),
+}
+
+impl Traffic {
+    // Route the HTTP traffic of every client built from a config through the cassette
+    pub fn apply(&self, config: &aws_types::SdkConfig) -> aws_types::SdkConfig {
+        let client = match self {
+            Traffic::Live => return config.clone(),
+            // without a client of its own, a config gets the SDK's default HTTPS client
+            Traffic::Record(cassette) => SharedHttpClient::new(Recorder {
+                inner: config.http_client().unwrap_or_else(|| {
+                    aws_smithy_http_client::Builder::new()
+                        .tls_provider(tls::Provider::Rustls(CryptoMode::AwsLc))
+                        .build_https()
+                }),
+                cassette: cassette.clone(),
+            }),
+            Traffic::Replay(cassette) => SharedHttpClient::new(Replayer(cassette.clone())),
+        };
+        config.to_builder().http_client(client).build()
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self, Traffic::Replay(_))
+    }
+}
+
+#[derive(Debug)]
+struct Interaction {
+    operation: String,
+    request: Value,
+    response: Value,
+    replayed: bool,
+}
+
+// A cassette file holds one JSON object per line: a header with the version, then one line
+// per interaction, appended as it happens so the file is complete whenever the shell exits
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    file: Option<Mutex<File>>, // the file interactions are appended to, when recording
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl Cassette {
+    // Start an empty cassette, failing early when the file cannot be written
+    pub fn create(path: &Path) -> Result<Self> {
+        let failed =
+            |e: std::io::Error| ShellError::CassetteError(format!("{}: {}", path.display(), e));
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(failed)?;
+        writeln!(file, "{}", json!({ "version": CASSETTE_VERSION })).map_err(failed)?;
+        Ok(Cassette {
+            path: path.to_path_buf(),
+            file: Some(Mutex::new(file)),
+            interactions: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        let invalid =
+            |reason: String| ShellError::CassetteError(format!("{}: {}", path.display(), reason));
+        let contents = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+        let header: Value = serde_json::from_str(lines.next().unwrap_or_default())
+            .map_err(|e| invalid(e.to_string()))?;
+        if header["version"].as_u64() != Some(CASSETTE_VERSION) {
+            return Err(invalid("unsupported cassette version".into()));
+        }
+        let interactions = lines
+            .map(|line| {
+                let interaction: Value =
+                    serde_json::from_str(line).map_err(|e| invalid(e.to_string()))?;
+                Ok(Interaction {
+                    operation: interaction["operation"].as_str().unwrap_or_default().into(),
+                    request: interaction["request"].clone(),
+                    response: interaction["response"].clone(),
+                    replayed: false,
+                })
+            })
+            .collect::<Result<Vec<Interaction>>>()?;
+        Ok(Cassette {
+            path: path.to_path_buf(),
+            file: None,
+            interactions: Mutex::new(interactions),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn record(&self, interaction: Interaction) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let line = json!({
+            "operation": interaction.operation,
+            "request": interaction.request,
+            "response": interaction.response,
+        });
+        let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{}", line) {
+            warn!(path = %self.path.display(), error = %e, "could not record the request");
+        }
+    }
+
+    // The first recorded response not replayed yet for the same operation, preferring one
+    // whose request had the same body since generated tokens make bodies differ otherwise
+    fn replay(&self, operation: &str, body: &Value) -> Option<Value> {
+        let mut interactions = self.interactions.lock().unwrap_or_else(|e| e.into_inner());
+        let unreplayed = |i: &&mut Interaction| !i.replayed && i.operation == operation;
+        let same_body = interactions
+            .iter()
+            .position(|i| !i.replayed && i.operation == operation && i.request["body"] == *body);
+        let interaction = match same_body {
+            Some(position) => &mut interactions[position],
+            None => interactions.iter_mut().find(unreplayed)?,
+        };
+        interaction.replayed = true;
+        Some(interaction.response.clone())
+    }
+}
+
+#[derive(Debug)]
+struct Recorder {
+    inner: SharedHttpClient,
+    cassette: Arc<Cassette>,
+}
+
+impl HttpClient for Recorder {
+    fn http_connector(
+        &self,
+        settings: &HttpConnectorSettings,
+        components: &RuntimeComponents,
+    ) -> SharedHttpConnector {
+        SharedHttpConnector::new(RecordingConnector {
+            inner: self.inner.http_connector(settings, components),
+            cassette: self.cassette.clone(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct RecordingConnector {
+    inner: SharedHttpConnector,
+    cassette: Arc<Cassette>,
+}
+
+impl HttpConnector for RecordingConnector {
+    fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+        let operation = operation_name(&request);
+        let recorded_request = json!({
+            "method": request.method(),
+            "uri": request.uri(),
+            "headers": headers_to_json(request.headers()),
+            "body": body_to_json(request.body().bytes().unwrap_or_default()),
+        });
+        let inner = self.inner.clone();
+        let cassette = self.cassette.clone();
+        HttpConnectorFuture::new(async move {
+            let mut response = inner.call(request).await?;
+            // the body is read whole so that it can be both recorded and returned
+            let body = std::mem::replace(response.body_mut(), SdkBody::taken());
+            let bytes = ByteStream::new(body)
+                .collect()
+                .await
+                .map_err(|e| ConnectorError::io(e.into()))?
+                .into_bytes();
+            cassette.record(Interaction {
+                operation,
+                request: recorded_request,
+                response: json!({
+                    "status": response.status().as_u16(),
+                    "headers": headers_to_json(response.headers()),
+                    "body": body_to_json(&bytes),
+                }),
+                replayed: false,
+            });
+            *response.body_mut() = SdkBody::from(bytes);
+            Ok(response)
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Replayer(Arc<Cassette>);
+
+impl HttpClient for Replayer {
+    fn http_connector(
+        &self,
+        _settings: &HttpConnectorSettings,
+        _components: &RuntimeComponents,
+    ) -> SharedHttpConnector {
+        SharedHttpConnector::new(ReplayingConnector(self.0.clone()))
+    }
+}
+
+#[derive(Debug)]
+struct ReplayingConnector(Arc<Cassette>);
+
+impl HttpConnector for ReplayingConnector {
+    fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+        let operation = operation_name(&request);
+        let body = body_to_json(request.body().bytes().unwrap_or_default());
+        let result = match self.0.replay(&operation, &body) {
+            Some(recorded) => recorded_response(&recorded),
+            None => Err(ConnectorError::other(
+                format!("no recorded response left for {}", operation).into(),
+                None,
+            )),
+        };
+        HttpConnectorFuture::ready(result)
+    }
+}
+
+fn recorded_response(recorded: &Value) -> std::result::Result<HttpResponse, ConnectorError> {
+    let invalid = |reason: &str| ConnectorError::other(reason.to_string().into(), None);
+    let status = recorded["status"]
+        .as_u64()
+        .and_then(|s| StatusCode::try_from(s as u16).ok())
+        .ok_or_else(|| invalid("recorded response has no valid status"))?;
+    let mut response = HttpResponse::new(status, SdkBody::from(body_from_json(&recorded["body"])));
+    if let Some(headers) = recorded["headers"].as_object() {
+        for (name, value) in headers {
+            if let Some(value) = value.as_str() {
+                response
+                    .headers_mut()
+                    .append(name.clone(), value.to_string());
+            }
+        }
+    }
+    Ok(response)
+}
+
+// JSON protocols like Athena's and Glue's name the operation in a header; REST protocols
+// like S3's in the method and path, which does not depend on the endpoint
+fn operation_name(request: &HttpRequest) -> String {
+    if let Some(target) = request.headers().get("x-amz-target") {
+        return target.to_string();
+    }
+    let uri = request.uri();
+    let path = match uri.split_once("://") {
+        Some((_, rest)) => rest.find('/').map(|i| &rest[i..]).unwrap_or("/"),
+        None => uri,
+    };
+    format!("{} {}", request.method(), path)
+}
+
+fn headers_to_json(headers: &Headers) -> Value {
+    let mut map = Map::new();
+    for (name, value) in headers.iter() {
+        let value = match SCRUBBED_HEADERS.contains(&name.to_lowercase().as_str()) {
+            true => SCRUBBED,
+            false => value,
+        };
+        map.insert(name.to_string(), Value::String(value.to_string()));
+    }
+    Value::Object(map)
+}
+
+// Text bodies are recorded as text with credentials scrubbed, and binary ones such as
+// S3 objects base64-encoded so that they replay byte for byte
+fn body_to_json(bytes: &[u8]) -> Value {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Value::String(scrub_body(text)),
+        Err(_) => json!({ "base64": base64::encode(bytes) }),
+    }
+}
+
+fn body_from_json(body: &Value) -> Vec<u8> {
+    match body {
+        Value::String(text) => text.clone().into_bytes(),
+        _ => body["base64"]
+            .as_str()
+            .and_then(|encoded| base64::decode(encoded).ok())
+            .unwrap_or_default(),
+    }
+}
+
+// Blank out credentials in JSON, XML and form-encoded bodies
+fn scrub_body(body: &str) -> String {
+    if let Ok(mut value) = serde_json::from_str::<Value>(body) {
+        scrub_json(&mut value);
+        return value.to_string();
+    }
+    let mut body = body.to_string();
+    for field in SCRUBBED_FIELDS {
+        body = scrub_between(&body, &format!("<{}>", field), &format!("</{}>", field));
+        body = scrub_between(&body, &format!("{}=", field), "&");
+    }
+    body
+}
+
+fn scrub_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SCRUBBED_FIELDS.iter().any(|f| f.eq_ignore_ascii_case(key)) {
+                    *value = Value::String(SCRUBBED.into());
+                } else {
+                    scrub_json(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(scrub_json),
+        _ => {}
+    }
+}
+
+// Replace what follows every occurrence of start, up to end or the end of the text
+fn scrub_between(text: &str, start: &str, end: &str) -> String {
+    let mut scrubbed = String::new();
+    let mut rest = text;
+    while let Some(i) = rest.find(start) {
+        scrubbed.push_str(&rest[..i + start.len()]);
+        scrubbed.push_str(SCRUBBED);
+        rest = &rest[i + start.len()..];
+        rest = match rest.find(end) {
+            Some(j) => &rest[j..],
+            None => "",
+        };
+    }
+    scrubbed.push_str(rest);
+    scrubbed
+}
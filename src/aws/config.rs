@@ -2,10 +2,13 @@ use crate::aws::error::{Result, ShellError};
 use aws_config::{BehaviorVersion, stalled_stream_protection::StalledStreamProtectionConfig};
 use aws_runtime::env_config::file;
 use configparser::ini::Ini;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
+use tracing::{debug, info};
 
 const DEFAULT_CREDENTIAL_PATH_PREFIX: &str = ".aws/credentials";
+const DEFAULT_CONFIG_PATH_PREFIX: &str = ".aws/config";
 
 pub struct ConfigOptions {
     pub retry_attempts: u32,
@@ -24,47 +27,156 @@ impl Default for ConfigOptions {
 }
 
 pub fn get_credentials_path() -> Result<PathBuf> {
+    // Check env variables for the path, SSO_CREDENTIAL_PATH being this shell's older override
+    for var in ["SSO_CREDENTIAL_PATH", "AWS_SHARED_CREDENTIALS_FILE"] {
+        if let Ok(path) = std::env::var(var) {
+            return Ok(PathBuf::from(path));
+        }
+    }
+    // Fallback to home directory
+    let home = directories::BaseDirs::new().ok_or(ShellError::MissingHomeDirectory)?;
+    let home_dir = home.home_dir();
+
+    Ok(home_dir.join(DEFAULT_CREDENTIAL_PATH_PREFIX))
+}
+
+pub fn get_config_path() -> Result<PathBuf> {
     // Check env variable for the path
-    if let Ok(path) = std::env::var("SSO_CREDENTIAL_PATH") {
+    if let Ok(path) = std::env::var("AWS_CONFIG_FILE") {
         return Ok(PathBuf::from(path));
     }
     // Fallback to home directory
     let home = directories::BaseDirs::new().ok_or(ShellError::MissingHomeDirectory)?;
     let home_dir = home.home_dir();
 
-    Ok(home_dir.join(DEFAULT_CREDENTIAL_PATH_PREFIX))
+    Ok(home_dir.join(DEFAULT_CONFIG_PATH_PREFIX))
+}
+
+// Map a section header to a profile name. The credentials file uses bare profile names,
+// while the config file uses "[profile name]" for everything except "[default]" and also
+// holds non-profile sections such as "[sso-session name]" and "[services name]".
+fn profile_name(section: &str, kind: file::EnvConfigFileKind) -> Option<String> {
+    let section = section.trim();
+    match kind {
+        file::EnvConfigFileKind::Credentials => Some(section.to_string()),
+        file::EnvConfigFileKind::Config => match section.split_once(char::is_whitespace) {
+            Some(("profile", name)) if !name.trim().is_empty() => Some(name.trim().to_string()),
+            None if section == "default" => Some(section.to_string()),
+            _ => None,
+        },
+    }
 }
 
-pub fn get_aws_profile(path: &PathBuf) -> Result<Vec<String>> {
-    let mut config = Ini::new();
+fn read_profiles(path: &Path, kind: file::EnvConfigFileKind) -> Result<Vec<String>> {
+    // profile names are case sensitive
+    let mut config = Ini::new_cs();
     let _ = config
-        .load(&path)
-        .map_err(|e| ShellError::AwsDefaultCredentialFileNotFound(e.into()))?;
-    let profiles = config.sections();
+        .load(path)
+        .map_err(ShellError::AwsDefaultCredentialFileNotFound)?;
+    Ok(config
+        .sections()
+        .iter()
+        .filter_map(|section| profile_name(section, kind))
+        .collect())
+}
+
+// List the profiles defined in the credentials and config files, skipping a file that does not exist
+pub fn get_aws_profile(credentials_path: &Path, config_path: &Path) -> Result<Vec<String>> {
+    let mut profiles: Vec<String> = Vec::new();
+    let files = [
+        (credentials_path, file::EnvConfigFileKind::Credentials),
+        (config_path, file::EnvConfigFileKind::Config),
+    ];
+    for (path, kind) in files.iter().filter(|(path, _)| path.exists()) {
+        for profile in read_profiles(path, *kind)? {
+            if !profiles.contains(&profile) {
+                profiles.push(profile);
+            }
+        }
+    }
+    if profiles.is_empty() && !credentials_path.exists() && !config_path.exists() {
+        return Err(ShellError::AwsDefaultCredentialFileNotFound(format!(
+            "{} or {}",
+            credentials_path.display(),
+            config_path.display()
+        )));
+    }
     Ok(profiles)
 }
 
+// Read the settings of a profile from both files, where the credentials file takes
+// precedence as it does for the SDK. Empty values are skipped.
+pub fn get_profile_settings(profile: &str) -> Result<HashMap<String, String>> {
+    let config_section = match profile {
+        "default" => profile.to_string(),
+        _ => format!("profile {}", profile),
+    };
+    let mut settings: HashMap<String, String> = HashMap::new();
+    for (path, section) in [
+        (get_credentials_path()?, profile.to_string()),
+        (get_config_path()?, config_section),
+    ] {
+        if !path.exists() {
+            continue;
+        }
+        let mut file = Ini::new_cs();
+        let _ = file
+            .load(&path)
+            .map_err(ShellError::AwsDefaultCredentialFileNotFound)?;
+        let Some(values) = file.get_map_ref().get(&section) else {
+            continue;
+        };
+        for (key, value) in values {
+            if let Some(value) = value.as_ref().filter(|v| !v.is_empty()) {
+                settings.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+    }
+    Ok(settings)
+}
+
+// Whether a profile gets its credentials from IAM Identity Center, so "aws sso login" can renew them
+pub fn is_sso_profile(profile: &str) -> Result<bool> {
+    let settings = get_profile_settings(profile)?;
+    Ok(settings.contains_key("sso_session") || settings.contains_key("sso_start_url"))
+}
+
+// Describe where the default provider chain will find credentials when no profile is used
+pub fn default_chain_source() -> &'static str {
+    let is_set = |name: &str| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+    if is_set("AWS_ACCESS_KEY_ID") {
+        "environment variables"
+    } else if is_set("AWS_WEB_IDENTITY_TOKEN_FILE") {
+        "web identity token"
+    } else if is_set("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI")
+        || is_set("AWS_CONTAINER_CREDENTIALS_FULL_URI")
+    {
+        "container credentials endpoint"
+    } else {
+        "default provider chain (instance metadata)"
+    }
+}
+
+// Build the SDK config for a named profile, or from the default provider chain when profile is None.
+// A region overrides the one the profile or environment sets.
 pub async fn build_config(
-    profile: &str,
+    profile: Option<&str>,
+    region: Option<&str>,
     timeout: u64,
     no_stall_protection: bool,
 ) -> Result<aws_types::SdkConfig> {
     // protect against invalid timeout values
-    if timeout <= 0 {
+    if timeout == 0 {
         return Err(ShellError::InvalidTimeout(timeout));
     }
-    // fail early if credential path is invalid
-    let cred_path = get_credentials_path()?;
-    if !cred_path.exists() {
-        return Err(ShellError::AwsCredentialFileNotFound(
-            cred_path.display().to_string(),
-        ));
-    }
-    println!("Loading credential file from: {}", cred_path.display());
     // fail early if profile is invalid
-    let profiles = get_aws_profile(&cred_path)?;
-    if !profiles.contains(&String::from(profile)) {
-        return Err(ShellError::AwsProfileNotFound(profile.into()));
+    let cred_path = get_credentials_path()?;
+    let config_path = get_config_path()?;
+    if let Some(profile) = profile {
+        let profiles = get_aws_profile(&cred_path, &config_path)?;
+        if !profiles.contains(&String::from(profile)) {
+            return Err(ShellError::AwsProfileNotFound(profile.into()));
+        }
     }
     // proceed to create the configuration
     let config_options = ConfigOptions::default();
@@ -83,24 +195,51 @@ pub async fn build_config(
         ))
         .build();
 
-    let profile_file = file::EnvConfigFiles::builder()
-        .with_file(
-            file::EnvConfigFileKind::Credentials,
-            get_credentials_path()?,
-        )
-        .build();
-
     let mut config_builder = aws_config::defaults(BehaviorVersion::latest())
-        .profile_files(profile_file)
-        .profile_name(profile)
         .timeout_config(timeout_config)
         .retry_config(retry_config);
 
+    if let Some(profile) = profile {
+        // register both files so SSO, assume-role and credential_process profiles from the config file resolve
+        let mut profile_files = file::EnvConfigFiles::builder();
+        for (path, kind) in [
+            (&cred_path, file::EnvConfigFileKind::Credentials),
+            (&config_path, file::EnvConfigFileKind::Config),
+        ] {
+            if path.exists() {
+                debug!(kind = ?kind, path = %path.display(), "loading profile file");
+                profile_files = profile_files.with_file(kind, path);
+            }
+        }
+        config_builder = config_builder
+            .profile_files(profile_files.build())
+            .profile_name(profile);
+    }
+
+    if let Some(region) = region {
+        config_builder = config_builder.region(aws_config::Region::new(region.to_string()));
+    }
+
     if no_stall_protection {
         config_builder =
             config_builder.stalled_stream_protection(StalledStreamProtectionConfig::disabled());
     }
-    println!("Loaded profile: {} from: {}", profile, &cred_path.display());
+    // the credential source is reported to the user, not only to the log
+    match profile {
+        Some(profile) => {
+            println!("Loaded profile: {}", profile);
+            info!(profile, "loaded profile");
+        }
+        None => {
+            let source = default_chain_source();
+            println!("Using credentials from: {}", source);
+            info!(source, "using the default credential chain");
+        }
+    }
+    if let Some(region) = region {
+        println!("Using region: {}", region);
+        info!(region, "using region");
+    }
 
     Ok(config_builder.load().await)
 }
@@ -0,0 +1,67 @@
+use aws_types::service_config::{LoadServiceConfig, ServiceConfigKey};
+use tracing::info;
+
+// Endpoint URLs that replace the ones the SDK resolves for a service, e.g. VPC interface
+// endpoints or local emulators. The SDK itself already reads AWS_ENDPOINT_URL_ATHENA,
+// AWS_ENDPOINT_URL_GLUE and AWS_ENDPOINT_URL_S3, and the endpoint_url settings of the
+// config file; these take precedence over both.
+#[derive(Clone, Debug, Default)]
+pub struct Endpoints {
+    pub athena: Option<String>,
+    pub glue: Option<String>,
+    pub s3: Option<String>,
+    pub s3_force_path_style: bool, // address buckets in the path, as local emulators expect
+}
+
+impl Endpoints {
+    fn get(&self, service_id: &str) -> Option<&String> {
+        match service_id {
+            "Athena" => self.athena.as_ref(),
+            "Glue" => self.glue.as_ref(),
+            "S3" => self.s3.as_ref(),
+            _ => None,
+        }
+    }
+
+    // Add the endpoint URLs to a config, so every client built from it uses them
+    pub fn apply(&self, config: &aws_types::SdkConfig) -> aws_types::SdkConfig {
+        if self.athena.is_none() && self.glue.is_none() && self.s3.is_none() {
+            return config.clone();
+        }
+        for (service, url) in [
+            ("Athena", &self.athena),
+            ("Glue", &self.glue),
+            ("S3", &self.s3),
+        ] {
+            if let Some(url) = url {
+                info!(service, url = %url, "using endpoint override");
+            }
+        }
+        config
+            .to_builder()
+            .service_config(EndpointOverrides {
+                endpoints: self.clone(),
+                fallback: config.clone(),
+            })
+            .build()
+    }
+}
+
+// Answers the endpoint_url setting of the overridden services, and everything else from
+// the environment and config file like the SDK's default service config does
+#[derive(Debug)]
+struct EndpointOverrides {
+    endpoints: Endpoints,
+    fallback: aws_types::SdkConfig,
+}
+
+impl LoadServiceConfig for EndpointOverrides {
+    fn load_config(&self, key: ServiceConfigKey<'_>) -> Option<String> {
+        if key.profile() == "endpoint_url"
+            && let Some(url) = self.endpoints.get(key.service_id())
+        {
+            return Some(url.clone());
+        }
+        self.fallback.service_config()?.load_config(key)
+    }
+}
@@ -1,21 +1,123 @@
+use aws_credential_types::provider::error::{CredentialsError, TokenError};
+use aws_smithy_runtime_api::client::result::SdkError;
+use aws_smithy_types::error::metadata::ProvideErrorMetadata;
 use thiserror::Error;
+use tracing::debug;
+
+// Error codes AWS services return when a request is signed with expired credentials
+const EXPIRED_CREDENTIALS_CODES: &[&str] =
+    &["ExpiredToken", "ExpiredTokenException", "RequestExpired"];
+const ACCESS_DENIED_CODES: &[&str] = &[
+    "AccessDenied",
+    "AccessDeniedException",
+    "UnauthorizedOperation",
+    "UnrecognizedClientException",
+    "InvalidClientTokenId",
+    "SignatureDoesNotMatch",
+];
+const THROTTLING_CODES: &[&str] = &[
+    "Throttling",
+    "ThrottlingException",
+    "TooManyRequestsException",
+    "RequestLimitExceeded",
+    "SlowDown",
+];
+const NOT_FOUND_CODES: &[&str] = &[
+    "EntityNotFoundException",
+    "ResourceNotFoundException",
+    "MetadataException",
+    "NoSuchBucket",
+    "NoSuchKey",
+];
+const INVALID_REQUEST_CODES: &[&str] = &[
+    "InvalidRequestException",
+    "InvalidInputException",
+    "ValidationException",
+    "ValidationError",
+];
 
 #[derive(Error, Debug)]
 pub enum ShellError {
     #[error("Generic Athena SDK error: {0}")]
-    AthenaSdkGenericError(#[from] aws_sdk_athena::Error),
+    AthenaSdkGenericError(Box<aws_sdk_athena::Error>),
 
     #[error("Generic S3 SDK error: {0}")]
-    S3SdkGenericError(#[from] aws_sdk_s3::Error),
+    S3SdkGenericError(Box<aws_sdk_s3::Error>),
 
     #[error("Generic Glue SDK error: {0}")]
-    GlueSdkGenericError(#[from] aws_sdk_glue::Error),
+    GlueSdkGenericError(Box<aws_sdk_glue::Error>),
+
+    #[error("Generic STS SDK error: {0}")]
+    StsSdkGenericError(Box<aws_sdk_sts::Error>),
+
+    #[error(
+        "{service} denied access: {message} (check the IAM permissions of the profile or role)"
+    )]
+    AccessDenied {
+        service: &'static str,
+        message: String,
+    },
+
+    #[error("{service} throttled the request: {message} (wait a moment and try again)")]
+    Throttled {
+        service: &'static str,
+        message: String,
+    },
+
+    #[error(
+        "{service} rejected the request{}: {message} (check the statement and its parameters)",
+        error_code_suffix(.code)
+    )]
+    InvalidRequest {
+        service: &'static str,
+        code: Option<String>, // Athena's AthenaErrorCode
+        message: String,
+    },
+
+    #[error("Not found in {service}: {message} (list what exists with \\ld and \\lt)")]
+    NotFound {
+        service: &'static str,
+        message: String,
+    },
+
+    #[error(
+        "The credentials have expired: {message} (renew them, e.g. with aws sso login, and try again)"
+    )]
+    ExpiredCredentials { message: String },
 
-    #[error("Query execution failed for ID: {execution_id}")]
-    QueryFailed { execution_id: String },
+    #[error(
+        "Cannot load the credentials: {message} (check the profile, or log in again, e.g. with aws sso login)"
+    )]
+    CredentialsUnavailable { message: String },
 
-    #[error("Query timeout after {attempts} attempts")]
-    QueryTimeout { attempts: i32 },
+    #[error(
+        "Cannot reach {service}: {message} (check the network connection, proxy and endpoint URL)"
+    )]
+    Network {
+        service: &'static str,
+        message: String,
+    },
+
+    #[error(
+        "Workgroup {workgroup} is saturated: Athena still throttled the statement after {attempts} attempts (wait for running queries to finish or lower --max-in-flight)"
+    )]
+    WorkgroupSaturated { workgroup: String, attempts: u32 },
+
+    #[error("Local query engine error: {0}")]
+    LocalEngineError(#[from] datafusion::error::DataFusionError),
+
+    // reason is left out when it was already printed, e.g. with the error position highlighted
+    #[error(
+        "Query execution failed for ID: {execution_id}{}",
+        reason.as_deref().map(|r| format!(": {}", r)).unwrap_or_default()
+    )]
+    QueryFailed {
+        execution_id: String,
+        reason: Option<String>,
+    },
+
+    #[error("Query {execution_id} was cancelled")]
+    QueryCancelled { execution_id: String },
 
     #[error("Missing query execution data")]
     MissingData,
@@ -26,20 +128,210 @@ pub enum ShellError {
     #[error("Invalid timeout value: {0}")]
     InvalidTimeout(u64),
 
-    #[error("Invalid service: {0}")]
-    InvalidService(String),
-
     #[error("Cannot convert from UTF-8: {0}")]
     FromUtf8ConversionError(#[from] std::string::FromUtf8Error),
 
     #[error("Credentials file not found: {0}")]
     AwsDefaultCredentialFileNotFound(String),
 
-    #[error("Credentials file not found: {0}")]
-    AwsCredentialFileNotFound(String),
-
     #[error("Profile name not found: {0}")]
     AwsProfileNotFound(String),
+
+    #[error("Invalid profile configuration: {0}")]
+    InvalidProfileConfig(String),
+
+    #[error("Prompt failed: {0}")]
+    PromptFailed(#[from] inquire::InquireError),
+
+    #[error("Undefined variable: {0}")]
+    UndefinedVariable(String),
+
+    #[error("Invalid variable name: {0}")]
+    InvalidVariableName(String),
+
+    #[error("Cannot read local data: {0}")]
+    LocalDataError(String),
+
+    #[error("Statement has {placeholders} placeholder(s) but {values} value(s) were bound")]
+    ParameterCountMismatch { placeholders: usize, values: usize },
+
+    #[error("{0} is not available with the local backend")]
+    UnsupportedByBackend(String),
+
+    #[error("{0} is not available when replaying a cassette")]
+    UnsupportedInReplay(String),
+
+    #[error("Cannot use cassette {0}")]
+    CassetteError(String),
+
+    #[error("Query returned no rows")]
+    NoRowsReturned,
+
+    #[error("Query returned {0} rows, expected exactly one")]
+    TooManyRowsReturned(usize),
+}
+
+impl ShellError {
+    // The service rejected the request because too many are in flight for the account or workgroup
+    pub fn is_throttling(&self) -> bool {
+        matches!(self, ShellError::Throttled { .. })
+    }
+
+    // The request failed because the session's credentials have expired, either rejected by
+    // the service or refused by a credentials provider such as an expired SSO token
+    pub fn is_expired_credentials(&self) -> bool {
+        matches!(self, ShellError::ExpiredCredentials { .. })
+    }
+}
+
+// The SDK errors are boxed to keep ShellError, and every Result carrying it, small
+macro_rules! boxed_sdk_error {
+    ($($error:ty => $variant:ident),* $(,)?) => {
+        $(impl From<$error> for ShellError {
+            fn from(e: $error) -> Self {
+                ShellError::$variant(Box::new(e))
+            }
+        })*
+    };
+}
+
+boxed_sdk_error! {
+    aws_sdk_athena::Error => AthenaSdkGenericError,
+    aws_sdk_s3::Error => S3SdkGenericError,
+    aws_sdk_glue::Error => GlueSdkGenericError,
+    aws_sdk_sts::Error => StsSdkGenericError,
+}
+
+pub fn athena_error<E, R>(e: SdkError<E, R>) -> ShellError
+where
+    E: std::error::Error + 'static,
+    R: std::fmt::Debug + 'static,
+    aws_sdk_athena::Error: From<SdkError<E, R>>,
+{
+    if let Some(failure) = dispatch_failure("Athena", &e) {
+        return failure;
+    }
+    let e = aws_sdk_athena::Error::from(e);
+    let code = match &e {
+        aws_sdk_athena::Error::InvalidRequestException(e) => {
+            e.athena_error_code().map(String::from)
+        }
+        _ => None,
+    };
+    classify("Athena", e, code)
+}
+
+pub fn glue_error<E, R>(e: SdkError<E, R>) -> ShellError
+where
+    E: std::error::Error + 'static,
+    R: std::fmt::Debug + 'static,
+    aws_sdk_glue::Error: From<SdkError<E, R>>,
+{
+    dispatch_failure("Glue", &e)
+        .unwrap_or_else(|| classify("Glue", aws_sdk_glue::Error::from(e), None))
+}
+
+pub fn s3_error<E, R>(e: SdkError<E, R>) -> ShellError
+where
+    E: std::error::Error + 'static,
+    R: std::fmt::Debug + 'static,
+    aws_sdk_s3::Error: From<SdkError<E, R>>,
+{
+    dispatch_failure("S3", &e).unwrap_or_else(|| classify("S3", aws_sdk_s3::Error::from(e), None))
+}
+
+pub fn sts_error<E, R>(e: SdkError<E, R>) -> ShellError
+where
+    E: std::error::Error + 'static,
+    R: std::fmt::Debug + 'static,
+    aws_sdk_sts::Error: From<SdkError<E, R>>,
+{
+    dispatch_failure("STS", &e)
+        .unwrap_or_else(|| classify("STS", aws_sdk_sts::Error::from(e), None))
+}
+
+// A request that timed out or was never sent. These carry no error code and become opaque
+// unhandled errors once converted into a service's error type, so they are sorted out here.
+// Credentials providers fail at dispatch too, e.g. when an SSO token has expired.
+fn dispatch_failure<E, R>(service: &'static str, e: &SdkError<E, R>) -> Option<ShellError>
+where
+    E: std::error::Error + 'static,
+    R: std::fmt::Debug + 'static,
+{
+    if !matches!(e, SdkError::TimeoutError(_) | SdkError::DispatchFailure(_)) {
+        return None;
+    }
+    debug!(service, error = ?e, "AWS request was not completed");
+    let message = root_cause(e);
+    if is_credentials_failure(e) {
+        return Some(match message.to_lowercase().contains("expired") {
+            true => ShellError::ExpiredCredentials { message },
+            false => ShellError::CredentialsUnavailable { message },
+        });
+    }
+    Some(ShellError::Network { service, message })
+}
+
+// Sort a failed AWS request into the failures the shell can explain in one line, keeping
+// the service's own error for the rest
+fn classify<E>(service: &'static str, e: E, code: Option<String>) -> ShellError
+where
+    E: ProvideErrorMetadata + std::error::Error + 'static,
+    ShellError: From<E>,
+{
+    debug!(service, error = ?e, "AWS request failed");
+    let message = match e.message() {
+        Some(message) => message.to_string(),
+        None => root_cause(&e),
+    };
+    let error_code = e.code().unwrap_or_default();
+    if EXPIRED_CREDENTIALS_CODES.contains(&error_code) {
+        return ShellError::ExpiredCredentials { message };
+    }
+    if ACCESS_DENIED_CODES.contains(&error_code) {
+        return ShellError::AccessDenied { service, message };
+    }
+    if THROTTLING_CODES.contains(&error_code) {
+        return ShellError::Throttled { service, message };
+    }
+    if NOT_FOUND_CODES.contains(&error_code) {
+        return ShellError::NotFound { service, message };
+    }
+    if INVALID_REQUEST_CODES.contains(&error_code) {
+        return ShellError::InvalidRequest {
+            service,
+            code,
+            message,
+        };
+    }
+    ShellError::from(e)
+}
+
+// The innermost error of a chain, which says what actually went wrong; credentials provider
+// and connection failures carry no error code, only a message further down the chain
+fn root_cause(e: &(dyn std::error::Error + 'static)) -> String {
+    let mut cause = e;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause.to_string()
+}
+
+fn is_credentials_failure(e: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(e);
+    while let Some(e) = source {
+        if e.is::<CredentialsError>() || e.is::<TokenError>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+fn error_code_suffix(code: &Option<String>) -> String {
+    code.as_ref()
+        .map(|code| format!(" ({})", code))
+        .unwrap_or_default()
 }
 
 pub type Result<T> = std::result::Result<T, ShellError>;
@@ -1,14 +1,71 @@
-use crate::aws::config::build_config;
-use crate::aws::error::{Result, ShellError};
+use crate::aws::error::{Result, ShellError, glue_error};
+use crate::aws::request_log::RequestLog;
 use aws_sdk_glue::Client as GlueClient;
+use aws_sdk_glue::types::Table;
+use aws_smithy_types::DateTime;
 
-const RETRY_MAX_ATTEMPTS: i32 = 5;
+pub struct Partition {
+    pub values: Vec<String>, // in the order of the table's partition keys
+    pub location: Option<String>,
+    pub creation_time: Option<DateTime>,
+}
+
+pub struct Column {
+    pub name: String,
+    pub data_type: String,
+    pub comment: Option<String>,
+}
+
+// A table of the Glue Data Catalog with its columns, partition keys last
+pub struct TableInfo {
+    pub database: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub table_type: Option<String>, // e.g. EXTERNAL_TABLE or VIRTUAL_VIEW
+    pub location: Option<String>,
+    pub owner: Option<String>,
+    pub update_time: Option<DateTime>,
+    pub columns: Vec<Column>,
+}
+
+impl From<&Table> for TableInfo {
+    fn from(table: &Table) -> Self {
+        let columns = table
+            .storage_descriptor()
+            .map(|sd| sd.columns())
+            .unwrap_or_default()
+            .iter()
+            .chain(table.partition_keys())
+            .map(|column| Column {
+                name: column.name().to_string(),
+                data_type: column.r#type().unwrap_or_default().to_string(),
+                comment: column.comment().map(String::from),
+            })
+            .collect();
+        TableInfo {
+            database: table.database_name().unwrap_or_default().to_string(),
+            name: table.name().to_string(),
+            description: table.description().map(String::from),
+            table_type: table.table_type().map(String::from),
+            location: table
+                .storage_descriptor()
+                .and_then(|sd| sd.location())
+                .map(String::from),
+            owner: table.owner().map(String::from),
+            update_time: table.update_time().cloned(),
+            columns,
+        }
+    }
+}
 
 pub struct GlueService(GlueClient);
 
 impl GlueService {
     pub async fn new(config: &aws_types::SdkConfig) -> Result<Self> {
-        let client = GlueClient::new(&config);
+        let glue_config = aws_sdk_glue::config::Builder::from(config)
+            .interceptor(RequestLog)
+            .build();
+        let client = GlueClient::from_conf(glue_config);
         Ok(GlueService(client))
     }
 
@@ -22,10 +79,7 @@ impl GlueService {
                 request = request.next_token(token);
             }
 
-            let response = request.send().await.map_err(|e| {
-                eprintln!("AWS Error Details: {:?}", e);
-                ShellError::GlueSdkGenericError(e.into())
-            })?;
+            let response = request.send().await.map_err(glue_error)?;
 
             for table in response.table_list() {
                 tables.push(table.name().into())
@@ -38,4 +92,98 @@ impl GlueService {
         }
         Ok(tables)
     }
+
+    pub async fn list_databases(&self) -> Result<Vec<String>> {
+        let mut databases: Vec<String> = Vec::new();
+        let mut response = self.0.get_databases().into_paginator().send();
+        while let Some(page) = response.next().await {
+            let page = page.map_err(glue_error)?;
+            for database in page.database_list() {
+                databases.push(database.name().into());
+            }
+        }
+        Ok(databases)
+    }
+
+    // Tables of a database with their columns
+    pub async fn get_tables(&self, database: &str) -> Result<Vec<TableInfo>> {
+        let mut tables: Vec<TableInfo> = Vec::new();
+        let mut response = self
+            .0
+            .get_tables()
+            .database_name(database)
+            .into_paginator()
+            .send();
+        while let Some(page) = response.next().await {
+            let page = page.map_err(glue_error)?;
+            tables.extend(page.table_list().iter().map(TableInfo::from));
+        }
+        Ok(tables)
+    }
+
+    // Tables of every database whose name, description, columns or properties mention text
+    pub async fn search_tables(&self, text: &str) -> Result<Vec<TableInfo>> {
+        let mut tables: Vec<TableInfo> = Vec::new();
+        let mut response = self
+            .0
+            .search_tables()
+            .search_text(text)
+            .into_paginator()
+            .send();
+        while let Some(page) = response.next().await {
+            let page = page.map_err(glue_error)?;
+            tables.extend(page.table_list().iter().map(TableInfo::from));
+        }
+        Ok(tables)
+    }
+
+    // Names of the partition keys of a table, in order
+    pub async fn partition_keys(&self, database: &str, table: &str) -> Result<Vec<String>> {
+        let response = self
+            .0
+            .get_table()
+            .database_name(database)
+            .name(table)
+            .send()
+            .await
+            .map_err(glue_error)?;
+        let table = response.table().ok_or(ShellError::MissingData)?;
+        Ok(table
+            .partition_keys()
+            .iter()
+            .map(|key| key.name().to_string())
+            .collect())
+    }
+
+    // Partitions of a table, only those matching expression when given, e.g. "year = '2024'"
+    pub async fn list_partitions(
+        &self,
+        database: &str,
+        table: &str,
+        expression: Option<&str>,
+    ) -> Result<Vec<Partition>> {
+        let mut partitions: Vec<Partition> = Vec::new();
+        let mut response = self
+            .0
+            .get_partitions()
+            .database_name(database)
+            .table_name(table)
+            .set_expression(expression.map(String::from))
+            .into_paginator()
+            .send();
+        while let Some(page) = response.next().await {
+            let page = page.map_err(glue_error)?;
+            for partition in page.partitions() {
+                partitions.push(Partition {
+                    values: partition.values().to_vec(),
+                    location: partition
+                        .storage_descriptor()
+                        .and_then(|sd| sd.location())
+                        .map(String::from),
+                    creation_time: partition.creation_time().cloned(),
+                });
+            }
+        }
+        Ok(partitions)
+    }
 }
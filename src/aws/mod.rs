@@ -1,5 +1,10 @@
-#![allow(unused)]
 pub mod athena;
+pub mod cache;
+pub mod cassette;
 pub mod config;
+pub mod endpoint;
 pub mod error;
 pub mod glue;
+pub mod request_log;
+pub mod s3;
+pub mod sts;
@@ -0,0 +1,127 @@
+use aws_smithy_runtime_api::box_error::BoxError;
+use aws_smithy_runtime_api::client::interceptors::Intercept;
+use aws_smithy_runtime_api::client::interceptors::context::{
+    BeforeSerializationInterceptorContextRef, BeforeTransmitInterceptorContextRef,
+    FinalizerInterceptorContextRef,
+};
+use aws_smithy_runtime_api::client::orchestrator::Metadata;
+use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+use aws_smithy_types::config_bag::{ConfigBag, Storable, StoreReplace};
+use std::time::Instant;
+use tracing::{Span, debug, field, info, info_span};
+
+// Response headers that carry the request ID, of JSON services like Athena and Glue, and of S3
+const REQUEST_ID_HEADERS: [&str; 2] = ["x-amzn-requestid", "x-amz-request-id"];
+
+// Opens a span for every AWS request a client sends, logging each attempt and, once the request
+// completes, its request ID, latency and number of retries
+#[derive(Debug)]
+pub struct RequestLog;
+
+#[derive(Clone, Debug)]
+struct RequestState {
+    span: Span,
+    started: Instant,
+    attempts: u32,
+}
+
+impl Storable for RequestState {
+    type Storer = StoreReplace<Self>;
+}
+
+impl Intercept for RequestLog {
+    fn name(&self) -> &'static str {
+        "RequestLog"
+    }
+
+    fn read_before_execution(
+        &self,
+        _context: &BeforeSerializationInterceptorContextRef<'_>,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        // the operation's metadata is only in the config bag once the request is serialized
+        let span = info_span!(
+            "aws_request",
+            service = field::Empty,
+            operation = field::Empty,
+            request_id = field::Empty,
+            latency_ms = field::Empty,
+            retries = field::Empty,
+        );
+        cfg.interceptor_state().store_put(RequestState {
+            span,
+            started: Instant::now(),
+            attempts: 0,
+        });
+        Ok(())
+    }
+
+    fn read_before_attempt(
+        &self,
+        _context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if let Some(mut state) = cfg.load::<RequestState>().cloned() {
+            if state.attempts == 0
+                && let Some(metadata) = cfg.load::<Metadata>()
+            {
+                state.span.record("service", metadata.service());
+                state.span.record("operation", metadata.name());
+            }
+            state.attempts += 1;
+            state
+                .span
+                .in_scope(|| debug!(attempt = state.attempts, "sending request"));
+            cfg.interceptor_state().store_put(state);
+        }
+        Ok(())
+    }
+
+    fn read_after_attempt(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if let Some(state) = cfg.load::<RequestState>() {
+            let status = context.response().map(|r| r.status().as_u16());
+            state.span.in_scope(|| match failed(context) {
+                true => debug!(attempt = state.attempts, status, "attempt failed"),
+                false => debug!(attempt = state.attempts, status, "attempt succeeded"),
+            });
+        }
+        Ok(())
+    }
+
+    fn read_after_execution(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Some(state) = cfg.load::<RequestState>() else {
+            return Ok(());
+        };
+        let request_id = context.response().and_then(|response| {
+            REQUEST_ID_HEADERS
+                .iter()
+                .find_map(|name| response.headers().get(*name))
+        });
+        let span = &state.span;
+        if let Some(request_id) = request_id {
+            span.record("request_id", request_id);
+        }
+        span.record("latency_ms", state.started.elapsed().as_millis() as u64);
+        span.record("retries", state.attempts.saturating_sub(1));
+        span.in_scope(|| match failed(context) {
+            true => info!("request failed"),
+            false => info!("request completed"),
+        });
+        Ok(())
+    }
+}
+
+fn failed(context: &FinalizerInterceptorContextRef<'_>) -> bool {
+    matches!(context.output_or_error(), Some(Err(_)))
+}
@@ -0,0 +1,47 @@
+use crate::aws::error::{Result, s3_error};
+use crate::aws::request_log::RequestLog;
+use aws_sdk_s3::Client as S3Client;
+
+pub struct S3Service(S3Client);
+
+impl S3Service {
+    // Emulators usually serve buckets in the path rather than as a subdomain of the endpoint
+    pub async fn new(config: &aws_types::SdkConfig, force_path_style: bool) -> Result<Self> {
+        let s3_config = aws_sdk_s3::config::Builder::from(config)
+            .force_path_style(force_path_style)
+            .interceptor(RequestLog)
+            .build();
+        Ok(S3Service(S3Client::from_conf(s3_config)))
+    }
+
+    // Number of objects under a prefix, leaving out the "folder" markers some tools create.
+    // Objects are counted page by page rather than collected, since a prefix may hold millions.
+    pub async fn count_objects(&self, bucket: &str, prefix: &str) -> Result<usize> {
+        let mut count = 0;
+        let mut response = self
+            .0
+            .list_objects_v2()
+            .bucket(bucket)
+            .prefix(prefix)
+            .into_paginator()
+            .send();
+        while let Some(page) = response.next().await {
+            let page = page.map_err(s3_error)?;
+            count += page
+                .contents()
+                .iter()
+                .filter(|object| object.key().is_some_and(|key| !key.ends_with('/')))
+                .count();
+        }
+        Ok(count)
+    }
+}
+
+// Split an s3://bucket/prefix URI into the bucket and the prefix
+pub fn parse_s3_uri(uri: &str) -> Option<(&str, &str)> {
+    let path = uri.strip_prefix("s3://")?;
+    match path.split_once('/') {
+        Some((bucket, prefix)) => Some((bucket, prefix)),
+        None => Some((path, "")),
+    }
+}
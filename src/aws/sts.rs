@@ -0,0 +1,220 @@
+use crate::aws::cache;
+use crate::aws::config::{build_config, get_profile_settings};
+use crate::aws::error::{Result, ShellError, sts_error};
+use crate::aws::request_log::RequestLog;
+use aws_credential_types::Credentials;
+use aws_credential_types::provider::SharedCredentialsProvider;
+use aws_sdk_sts::Client as StsClient;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+const CACHE_DIR: &str = "athena-shell/credentials";
+const DEFAULT_SESSION_NAME: &str = "athena-shell";
+// Refresh cached credentials this long before they actually expire
+const EXPIRY_MARGIN: Duration = Duration::from_secs(300);
+
+// A profile that assumes a role and needs an MFA token code, which the SDK cannot prompt for
+pub struct MfaRoleProfile {
+    pub role_arn: String,
+    pub mfa_serial: String,
+    pub source_profile: String,
+    pub duration_seconds: Option<i32>,
+    pub role_session_name: Option<String>,
+}
+
+// Read the role and MFA settings of a profile, returning None unless it sets both role_arn and mfa_serial
+pub fn get_mfa_role_profile(profile: &str) -> Result<Option<MfaRoleProfile>> {
+    let settings = get_profile_settings(profile)?;
+    let get = |key: &str| settings.get(key).cloned();
+    let (Some(role_arn), Some(mfa_serial)) = (get("role_arn"), get("mfa_serial")) else {
+        return Ok(None);
+    };
+    let source_profile = get("source_profile").ok_or_else(|| {
+        ShellError::InvalidProfileConfig(format!(
+            "profile {} sets mfa_serial but no source_profile",
+            profile
+        ))
+    })?;
+    Ok(Some(MfaRoleProfile {
+        role_arn,
+        mfa_serial,
+        source_profile,
+        duration_seconds: get("duration_seconds").and_then(|d| d.parse().ok()),
+        role_session_name: get("role_session_name"),
+    }))
+}
+
+// Config of a source profile and the MFA device used with it to assume roles
+pub type MfaSource = (aws_types::SdkConfig, String);
+
+pub struct StsService(StsClient);
+
+impl StsService {
+    pub async fn new(config: &aws_types::SdkConfig) -> Result<Self> {
+        let sts_config = aws_sdk_sts::config::Builder::from(config)
+            .interceptor(RequestLog)
+            .build();
+        let client = StsClient::from_conf(sts_config);
+        Ok(StsService(client))
+    }
+
+    pub async fn assume_role(
+        &self,
+        role_arn: &str,
+        session_name: Option<&str>,
+        mfa: Option<(&str, &str)>, // (serial number, token code)
+        duration_seconds: Option<i32>,
+    ) -> Result<Credentials> {
+        let mut request = self
+            .0
+            .assume_role()
+            .role_arn(role_arn)
+            .role_session_name(session_name.unwrap_or(DEFAULT_SESSION_NAME))
+            .set_duration_seconds(duration_seconds);
+        if let Some((serial_number, token_code)) = mfa {
+            request = request.serial_number(serial_number).token_code(token_code);
+        }
+        let response = request.send().await.map_err(sts_error)?;
+        let credentials = response.credentials().ok_or(ShellError::MissingData)?;
+        Ok(Credentials::new(
+            credentials.access_key_id(),
+            credentials.secret_access_key(),
+            Some(credentials.session_token().to_string()),
+            Some(UNIX_EPOCH + Duration::from_secs(credentials.expiration().secs().max(0) as u64)),
+            "AssumeRole",
+        ))
+    }
+}
+
+// Assume a role, reusing credentials cached by an earlier session. token_code is only
+// called when the role needs an MFA token and nothing valid is cached.
+pub async fn assume_role_cached(
+    config: &aws_types::SdkConfig,
+    role_arn: &str,
+    mfa_serial: Option<&str>,
+    duration_seconds: Option<i32>,
+    session_name: Option<&str>,
+    token_code: impl AsyncFnOnce(&str) -> Result<String>,
+) -> Result<Credentials> {
+    if let Some(credentials) = load_cached_credentials(role_arn, mfa_serial) {
+        return Ok(credentials);
+    }
+    let token = match mfa_serial {
+        Some(serial) => Some(token_code(serial).await?),
+        None => None,
+    };
+    let sts_service = StsService::new(config).await?;
+    let credentials = sts_service
+        .assume_role(
+            role_arn,
+            session_name,
+            mfa_serial.zip(token.as_deref()),
+            duration_seconds,
+        )
+        .await?;
+    save_cached_credentials(role_arn, mfa_serial, &credentials);
+    Ok(credentials)
+}
+
+// Build the SDK config for a profile like build_config, assuming the profile's role ourselves
+// when it needs an MFA token. The source profile's config and MFA device are returned with it.
+pub async fn build_session_config(
+    profile: Option<&str>,
+    region: Option<&str>,
+    timeout: u64,
+    no_stall_protection: bool,
+    token_code: impl AsyncFnOnce(&str) -> Result<String>,
+) -> Result<(aws_types::SdkConfig, Option<MfaSource>)> {
+    let config = build_config(profile, region, timeout, no_stall_protection).await?;
+    let Some(profile) = profile else {
+        return Ok((config, None));
+    };
+    let Some(mfa_profile) = get_mfa_role_profile(profile)? else {
+        return Ok((config, None));
+    };
+    info!(
+        profile,
+        role_arn = %mfa_profile.role_arn,
+        mfa_serial = %mfa_profile.mfa_serial,
+        "profile assumes a role with MFA"
+    );
+    let source_config = build_config(
+        Some(&mfa_profile.source_profile),
+        region,
+        timeout,
+        no_stall_protection,
+    )
+    .await?;
+    let credentials = assume_role_cached(
+        &source_config,
+        &mfa_profile.role_arn,
+        Some(&mfa_profile.mfa_serial),
+        mfa_profile.duration_seconds,
+        mfa_profile.role_session_name.as_deref(),
+        token_code,
+    )
+    .await?;
+    Ok((
+        with_credentials(&config, credentials),
+        Some((source_config, mfa_profile.mfa_serial)),
+    ))
+}
+
+// Replace the credentials of a config, keeping its region, timeouts and retry settings
+pub fn with_credentials(
+    config: &aws_types::SdkConfig,
+    credentials: Credentials,
+) -> aws_types::SdkConfig {
+    config
+        .to_builder()
+        .credentials_provider(SharedCredentialsProvider::new(credentials))
+        .build()
+}
+
+fn cache_path(role_arn: &str, mfa_serial: Option<&str>) -> Option<PathBuf> {
+    cache::cache_path(
+        CACHE_DIR,
+        &format!("{}_{}", role_arn, mfa_serial.unwrap_or("")),
+    )
+}
+
+// Temporary credentials cached by an earlier session, if they are still valid
+pub fn load_cached_credentials(role_arn: &str, mfa_serial: Option<&str>) -> Option<Credentials> {
+    let contents = std::fs::read_to_string(cache_path(role_arn, mfa_serial)?).ok()?;
+    let cached: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    let expiry = UNIX_EPOCH + Duration::from_secs(cached.get("expiration")?.as_u64()?);
+    if expiry <= SystemTime::now() + EXPIRY_MARGIN {
+        return None;
+    }
+    Some(Credentials::new(
+        cached.get("access_key_id")?.as_str()?,
+        cached.get("secret_access_key")?.as_str()?,
+        Some(cached.get("session_token")?.as_str()?.to_string()),
+        Some(expiry),
+        "AssumeRoleCache",
+    ))
+}
+
+// Cache temporary credentials until they expire
+pub fn save_cached_credentials(
+    role_arn: &str,
+    mfa_serial: Option<&str>,
+    credentials: &Credentials,
+) {
+    let Some(path) = cache_path(role_arn, mfa_serial) else {
+        return;
+    };
+    let expiration = credentials
+        .expiry()
+        .and_then(|e| e.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let contents = serde_json::json!({
+        "access_key_id": credentials.access_key_id(),
+        "secret_access_key": credentials.secret_access_key(),
+        "session_token": credentials.session_token(),
+        "expiration": expiration,
+    });
+    cache::save(&path, &contents.to_string());
+}
@@ -0,0 +1,345 @@
+use crate::aws::athena::{QueryContext, QuerySummary};
+use crate::aws::error::{Result, ShellError};
+use crate::backend::QueryBackend;
+use async_trait::async_trait;
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use datafusion::catalog::{CatalogProvider, MemoryCatalogProvider, MemorySchemaProvider};
+use datafusion::common::TableReference;
+use datafusion::prelude::{CsvReadOptions, ParquetReadOptions, SessionConfig, SessionContext};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::task::AbortHandle;
+use tracing::warn;
+
+// Catalog that unqualified database names refer to, like Athena's AwsDataCatalog
+const DEFAULT_CATALOG: &str = "awsdatacatalog";
+
+struct Execution {
+    summary: QuerySummary,
+    rows: Vec<Vec<String>>,
+    handle: Option<AbortHandle>,
+}
+
+// Runs statements offline with DataFusion against CSV and Parquet files laid out as
+// <dir>/<catalog>/<database>/<table>, where a table is a .csv or .parquet file or a
+// directory of them. Statements run in the background like Athena queries do.
+pub struct LocalBackend {
+    catalogs: SessionContext, // holds the registered tables, shared by every statement's session
+    default_catalog: String,
+    executions: Arc<Mutex<HashMap<String, Execution>>>,
+    next_id: AtomicUsize,
+}
+
+impl LocalBackend {
+    pub async fn open(dir: &Path) -> Result<Self> {
+        let catalogs = SessionContext::new_with_config(
+            SessionConfig::new().with_create_default_catalog_and_schema(false),
+        );
+        let mut catalog_names: Vec<String> = Vec::new();
+        for (catalog_name, catalog_path) in subdirectories(dir)? {
+            catalogs.register_catalog(&catalog_name, Arc::new(MemoryCatalogProvider::new()));
+            for (database, database_path) in subdirectories(&catalog_path)? {
+                if let Some(catalog) = catalogs.catalog(&catalog_name) {
+                    catalog.register_schema(&database, Arc::new(MemorySchemaProvider::new()))?;
+                }
+                for entry in read_dir(&database_path)? {
+                    let table = TableReference::full(
+                        catalog_name.as_str(),
+                        database.as_str(),
+                        table_name(&entry).as_str(),
+                    );
+                    register_table(&catalogs, table, &entry).await?;
+                }
+            }
+            catalog_names.push(catalog_name);
+        }
+        let default_catalog = match catalog_names.iter().find(|c| *c == DEFAULT_CATALOG) {
+            Some(catalog) => catalog.clone(),
+            None => catalog_names.first().cloned().ok_or_else(|| {
+                ShellError::LocalDataError(format!("no catalog directories in {}", dir.display()))
+            })?,
+        };
+        println!(
+            "Loaded {} catalog(s) from {}, default catalog: {}",
+            catalog_names.len(),
+            dir.display(),
+            default_catalog
+        );
+        Ok(LocalBackend {
+            catalogs,
+            default_catalog,
+            executions: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicUsize::new(0),
+        })
+    }
+
+    // A session over the registered catalogs that resolves unqualified tables in database
+    fn session(&self, database: &str) -> SessionContext {
+        let config = SessionConfig::new()
+            .with_create_default_catalog_and_schema(false)
+            .with_default_catalog_and_schema(&self.default_catalog, database.to_lowercase());
+        let session = SessionContext::new_with_config(config);
+        for name in self.catalogs.catalog_names() {
+            if let Some(catalog) = self.catalogs.catalog(&name) {
+                session.register_catalog(name, catalog);
+            }
+        }
+        session
+    }
+
+    fn catalog(&self, name: &str) -> Result<Arc<dyn CatalogProvider>> {
+        self.catalogs
+            .catalog(&name.to_lowercase())
+            .ok_or_else(|| ShellError::LocalDataError(format!("no such catalog: {}", name)))
+    }
+
+    fn with_execution<T>(
+        &self,
+        execution_id: &str,
+        f: impl FnOnce(&mut Execution) -> T,
+    ) -> Result<T> {
+        let mut executions = self.executions.lock().unwrap_or_else(|e| e.into_inner());
+        let execution = executions
+            .get_mut(execution_id)
+            .ok_or(ShellError::MissingData)?;
+        Ok(f(execution))
+    }
+}
+
+#[async_trait]
+impl QueryBackend for LocalBackend {
+    async fn invoke_query(
+        &self,
+        ctx: &QueryContext,
+        query: &str,
+        params: &[String],
+        _client_request_token: &str,
+    ) -> Result<String> {
+        let query = bind_params(query, params)?;
+        let session = self.session(&ctx.database);
+        let execution_id = format!("local-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        let summary = QuerySummary {
+            execution_id: execution_id.clone(),
+            state: "RUNNING".into(),
+            state_change_reason: None,
+            error_category: None,
+            error_type: None,
+            retryable: None,
+            data_scanned_bytes: None,
+            execution_time_ms: None,
+            reused_previous_result: false,
+        };
+        self.executions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                execution_id.clone(),
+                Execution {
+                    summary,
+                    rows: Vec::new(),
+                    handle: None,
+                },
+            );
+
+        let executions = self.executions.clone();
+        let id = execution_id.clone();
+        let handle = tokio::spawn(async move {
+            let started = Instant::now();
+            let result = run_query(&session, &query).await;
+            let mut executions = executions.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(execution) = executions.get_mut(&id) {
+                execution.summary.execution_time_ms = Some(started.elapsed().as_millis() as i64);
+                match result {
+                    Ok(rows) => {
+                        execution.summary.state = "SUCCEEDED".into();
+                        execution.rows = rows;
+                    }
+                    Err(e) => {
+                        execution.summary.state = "FAILED".into();
+                        execution.summary.state_change_reason = Some(e.to_string());
+                    }
+                }
+            }
+        });
+        self.with_execution(&execution_id, |execution| {
+            execution.handle = Some(handle.abort_handle())
+        })?;
+        Ok(execution_id)
+    }
+
+    async fn get_query_summary(&self, execution_id: &str) -> Result<QuerySummary> {
+        self.with_execution(execution_id, |execution| execution.summary.clone())
+    }
+
+    async fn get_query_results(&self, execution_id: &str) -> Result<Vec<Vec<String>>> {
+        self.with_execution(execution_id, |execution| execution.rows.clone())
+    }
+
+    async fn stop_query(&self, execution_id: &str) -> Result<()> {
+        self.with_execution(execution_id, |execution| {
+            if !execution.summary.is_finished() {
+                if let Some(handle) = &execution.handle {
+                    handle.abort();
+                }
+                execution.summary.state = "CANCELLED".into();
+            }
+        })
+    }
+
+    async fn list_catalogs(&self) -> Result<Vec<String>> {
+        let mut catalogs = self.catalogs.catalog_names();
+        catalogs.sort();
+        Ok(catalogs)
+    }
+
+    async fn list_databases(&self, catalog_name: &str) -> Result<Vec<String>> {
+        let mut databases = self.catalog(catalog_name)?.schema_names();
+        databases.sort();
+        Ok(databases)
+    }
+
+    async fn list_tables(&self, database: &str) -> Result<Vec<String>> {
+        let schema = self
+            .catalog(&self.default_catalog)?
+            .schema(&database.to_lowercase())
+            .ok_or_else(|| ShellError::LocalDataError(format!("no such database: {}", database)))?;
+        let mut tables = schema.table_names();
+        tables.sort();
+        Ok(tables)
+    }
+}
+
+// Run a statement and format its result like Athena does: a header row, then every value as text
+async fn run_query(
+    session: &SessionContext,
+    query: &str,
+) -> datafusion::error::Result<Vec<Vec<String>>> {
+    let df = session.sql(query).await?;
+    let mut rows: Vec<Vec<String>> = vec![
+        df.schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect(),
+    ];
+    let options = FormatOptions::default();
+    for batch in df.collect().await? {
+        let formatters = batch
+            .columns()
+            .iter()
+            .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+            .collect::<std::result::Result<Vec<ArrayFormatter>, _>>()?;
+        for row in 0..batch.num_rows() {
+            rows.push(
+                formatters
+                    .iter()
+                    .map(|f| f.value(row).to_string())
+                    .collect(),
+            );
+        }
+    }
+    Ok(rows)
+}
+
+// Replace the `?` placeholders outside string literals with the bound SQL literals, in order
+fn bind_params(query: &str, params: &[String]) -> Result<String> {
+    let mut bound = String::new();
+    let mut values = params.iter();
+    let mut placeholders = 0;
+    let mut in_quotes = false;
+    for c in query.chars() {
+        match c {
+            '\'' => {
+                in_quotes = !in_quotes;
+                bound.push(c);
+            }
+            '?' if !in_quotes => {
+                placeholders += 1;
+                if let Some(value) = values.next() {
+                    bound.push_str(value);
+                }
+            }
+            _ => bound.push(c),
+        }
+    }
+    if placeholders != params.len() {
+        return Err(ShellError::ParameterCountMismatch {
+            placeholders,
+            values: params.len(),
+        });
+    }
+    Ok(bound)
+}
+
+async fn register_table(
+    catalogs: &SessionContext,
+    table: TableReference,
+    path: &Path,
+) -> Result<()> {
+    let location = match path.is_dir() {
+        // a directory is read as a whole, so its path must end with a separator
+        true => format!("{}/", path.display()),
+        false => path.display().to_string(),
+    };
+    match table_format(path)? {
+        Some("parquet") => {
+            let options = ParquetReadOptions::default();
+            catalogs.register_parquet(table, &location, options).await?;
+        }
+        Some("csv") => {
+            let options = CsvReadOptions::new();
+            catalogs.register_csv(table, &location, options).await?;
+        }
+        _ => warn!(path = %path.display(), "skipping a table that is not .csv or .parquet"),
+    }
+    Ok(())
+}
+
+// The file format of a table file, or of the files in a table directory
+fn table_format(path: &Path) -> Result<Option<&'static str>> {
+    let extension_of = |path: &Path| match path.extension().and_then(|e| e.to_str()) {
+        Some("parquet") => Some("parquet"),
+        Some("csv") => Some("csv"),
+        _ => None,
+    };
+    if !path.is_dir() {
+        return Ok(extension_of(path));
+    }
+    Ok(read_dir(path)?.iter().find_map(|file| extension_of(file)))
+}
+
+fn table_name(path: &Path) -> String {
+    let name = match path.is_dir() {
+        true => path.file_name(),
+        false => path.file_stem(),
+    };
+    name.map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+// Subdirectories of a directory with their lower-cased names, as unquoted SQL identifiers are
+fn subdirectories(dir: &Path) -> Result<Vec<(String, std::path::PathBuf)>> {
+    Ok(read_dir(dir)?
+        .into_iter()
+        .filter(|path| path.is_dir())
+        .map(|path| (table_name(&path), path))
+        .collect())
+}
+
+fn read_dir(dir: &Path) -> Result<Vec<std::path::PathBuf>> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| ShellError::LocalDataError(format!("{}: {}", dir.display(), e)))?;
+    let mut paths: Vec<std::path::PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            !path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
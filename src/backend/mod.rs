@@ -0,0 +1,222 @@
+pub mod local;
+
+use crate::aws::athena::{AthenaService, QueryContext, QuerySummary};
+use crate::aws::error::{Result, ShellError};
+use crate::aws::glue::GlueService;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+
+use self::local::LocalBackend;
+
+const SUBMIT_MAX_ATTEMPTS: u32 = 8;
+const SUBMIT_INITIAL_BACKOFF_MS: u64 = 1000;
+const SUBMIT_MAX_BACKOFF_MS: u64 = 32000;
+const QUERY_RETRY_INITIAL_BACKOFF_SECS: u64 = 1;
+const QUERY_RETRY_MAX_BACKOFF_SECS: u64 = 30;
+// Statements that only read, so running them again cannot write their results twice
+const READ_ONLY_KEYWORDS: [&str; 4] = ["SELECT", "WITH", "SHOW", "DESCRIBE"];
+
+// How often a read-only statement that failed transiently is resubmitted, waiting longer each time
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+}
+
+impl RetryPolicy {
+    // Whether a statement that ended as summary after `retries` resubmissions is resubmitted again.
+    // A failed INSERT or CTAS may already have written files, so only read-only statements are.
+    pub fn should_retry(&self, query: &str, summary: &QuerySummary, retries: u32) -> bool {
+        retries < self.max_retries && summary.is_transient_failure() && is_read_only(query)
+    }
+
+    // Delay before the next resubmission: 1s, 2s, 4s and so on, up to 30s
+    pub fn backoff(&self, retries: u32) -> Duration {
+        let secs = QUERY_RETRY_INITIAL_BACKOFF_SECS << retries.min(5);
+        Duration::from_secs(secs.min(QUERY_RETRY_MAX_BACKOFF_SECS))
+    }
+}
+
+// Runs statements and lists the catalog, following Athena's asynchronous execution model:
+// a statement is submitted, polled until it reaches a final state, then its results are fetched
+#[async_trait]
+pub trait QueryBackend: Send + Sync {
+    // Submit a statement, binding params to its `?` placeholders, and return its execution ID.
+    // Submissions sharing a client request token start the statement only once.
+    async fn invoke_query(
+        &self,
+        ctx: &QueryContext,
+        query: &str,
+        params: &[String],
+        client_request_token: &str,
+    ) -> Result<String>;
+
+    async fn get_query_summary(&self, execution_id: &str) -> Result<QuerySummary>;
+
+    // Rows of a succeeded statement, starting with the column names
+    async fn get_query_results(&self, execution_id: &str) -> Result<Vec<Vec<String>>>;
+
+    async fn stop_query(&self, execution_id: &str) -> Result<()>;
+
+    async fn list_catalogs(&self) -> Result<Vec<String>>;
+
+    async fn list_databases(&self, catalog_name: &str) -> Result<Vec<String>>;
+
+    async fn list_tables(&self, database: &str) -> Result<Vec<String>>;
+
+    // Submit a statement, backing off with jitter while the workgroup throttles it. Every attempt
+    // sends the same token, so a submission whose response was lost does not run it twice.
+    // on_throttled is called with the delay before each retry.
+    async fn submit_query(
+        &self,
+        ctx: &QueryContext,
+        query: &str,
+        params: &[String],
+        on_throttled: &(dyn Fn(Duration) + Send + Sync),
+    ) -> Result<String> {
+        let token = client_request_token();
+        let mut backoff = SUBMIT_INITIAL_BACKOFF_MS;
+        let mut attempt = 1;
+        loop {
+            match self.invoke_query(ctx, query, params, &token).await {
+                Err(e) if e.is_throttling() && attempt < SUBMIT_MAX_ATTEMPTS => {
+                    // half the backoff plus a random part, so concurrent statements spread out
+                    let jitter = rand::random_range(0..=backoff / 2);
+                    let delay = Duration::from_millis(backoff / 2 + jitter);
+                    on_throttled(delay);
+                    tokio::time::sleep(delay).await;
+                    backoff = (backoff * 2).min(SUBMIT_MAX_BACKOFF_MS);
+                    attempt += 1;
+                }
+                Err(e) if e.is_throttling() => {
+                    return Err(ShellError::WorkgroupSaturated {
+                        workgroup: ctx.workgroup.clone(),
+                        attempts: attempt,
+                    });
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn get_query_state(&self, execution_id: &str) -> Result<String> {
+        Ok(self.get_query_summary(execution_id).await?.state)
+    }
+
+    // Poll until the statement reaches a final state, without an attempt limit
+    async fn wait_for_query(&self, execution_id: &str, poll_interval: u64) -> Result<QuerySummary> {
+        loop {
+            let summary = self.get_query_summary(execution_id).await?;
+            if summary.is_finished() {
+                return Ok(summary);
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(poll_interval)).await;
+        }
+    }
+}
+
+// Whether a statement starts with a read-only keyword, after any comments and parentheses
+fn is_read_only(query: &str) -> bool {
+    let mut rest = query.trim_start();
+    loop {
+        if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/").map_or("", |(_, rest)| rest);
+        } else if let Some(nested) = rest.strip_prefix('(') {
+            rest = nested;
+        } else {
+            break;
+        }
+        rest = rest.trim_start();
+    }
+    let keyword = rest
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or_default();
+    READ_ONLY_KEYWORDS
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(keyword))
+}
+
+// A random token of the 32 to 128 characters Athena accepts
+fn client_request_token() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+// Statements run on Athena while tables are listed from Glue, so that "\lt" and "\lt+",
+// which needs Glue's table details, always list the same tables
+pub struct AthenaBackend {
+    athena: AthenaService,
+    glue: GlueService,
+}
+
+impl AthenaBackend {
+    pub async fn new(config: &aws_types::SdkConfig) -> Result<Self> {
+        Ok(AthenaBackend {
+            athena: AthenaService::new(config).await?,
+            glue: GlueService::new(config).await?,
+        })
+    }
+}
+
+#[async_trait]
+impl QueryBackend for AthenaBackend {
+    async fn invoke_query(
+        &self,
+        ctx: &QueryContext,
+        query: &str,
+        params: &[String],
+        client_request_token: &str,
+    ) -> Result<String> {
+        self.athena
+            .invoke_query(ctx, query, params, client_request_token)
+            .await
+    }
+
+    async fn get_query_summary(&self, execution_id: &str) -> Result<QuerySummary> {
+        self.athena.get_query_summary(execution_id).await
+    }
+
+    async fn get_query_results(&self, execution_id: &str) -> Result<Vec<Vec<String>>> {
+        self.athena.get_query_results(execution_id).await
+    }
+
+    async fn stop_query(&self, execution_id: &str) -> Result<()> {
+        self.athena.stop_query(execution_id).await
+    }
+
+    async fn list_catalogs(&self) -> Result<Vec<String>> {
+        self.athena.list_catalogs().await
+    }
+
+    async fn list_databases(&self, catalog_name: &str) -> Result<Vec<String>> {
+        self.athena.list_databases(catalog_name).await
+    }
+
+    async fn list_tables(&self, database: &str) -> Result<Vec<String>> {
+        self.glue.list_tables(database).await
+    }
+}
+
+// The backend a session runs statements on, selected with --backend
+#[derive(Clone)]
+pub enum Backend {
+    Athena,
+    Local(Arc<LocalBackend>),
+}
+
+impl Backend {
+    // Athena clients are built from the session's current config, so they follow
+    // credential renewals and profile or region switches
+    pub async fn open(&self, config: &aws_types::SdkConfig) -> Result<Arc<dyn QueryBackend>> {
+        match self {
+            Backend::Athena => Ok(Arc::new(AthenaBackend::new(config).await?)),
+            Backend::Local(local) => Ok(local.clone()),
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        matches!(self, Backend::Local(_))
+    }
+}
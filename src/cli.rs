@@ -0,0 +1,122 @@
+use clap::{Parser, ValueEnum};
+use std::path::PathBuf;
+
+use crate::vars;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "athena-shell",
+    version,
+    about = "Run Amazon Athena queries from your shell"
+)]
+pub struct Cli {
+    /// Where statements run
+    #[arg(long, value_enum, default_value_t = BackendKind::Athena)]
+    pub backend: BackendKind,
+
+    /// Directory of <catalog>/<database>/<table> CSV and Parquet files for the local backend
+    #[arg(long, value_name = "PATH", default_value = ".")]
+    pub local_dir: PathBuf,
+
+    /// AWS profile to use (prompted when omitted)
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// AWS region (the profile or environment setting is used when omitted)
+    #[arg(long)]
+    pub region: Option<String>,
+
+    /// Athena endpoint URL, e.g. a VPC interface endpoint or a local emulator
+    #[arg(long, value_name = "URL")]
+    pub athena_endpoint_url: Option<String>,
+
+    /// Glue endpoint URL
+    #[arg(long, value_name = "URL")]
+    pub glue_endpoint_url: Option<String>,
+
+    /// S3 endpoint URL
+    #[arg(long, value_name = "URL")]
+    pub s3_endpoint_url: Option<String>,
+
+    /// Address S3 buckets in the URL path instead of the host name, as local emulators expect
+    #[arg(long)]
+    pub s3_force_path_style: bool,
+
+    /// Record every Athena, Glue and S3 request and response into a cassette file
+    #[arg(long, value_name = "PATH", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Answer AWS requests from a cassette recorded with --record, without AWS access
+    #[arg(long, value_name = "PATH")]
+    pub replay: Option<PathBuf>,
+
+    /// Log more: AWS requests and statements with -v, debug details with -vv, everything with -vvv
+    #[arg(short, long, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Log errors only
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Write the log to a file, appending, instead of to stderr
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Timeout in seconds (prompted when omitted)
+    #[arg(long)]
+    pub timeout: Option<u64>,
+
+    /// Database used as the query execution context
+    #[arg(short, long, default_value = "default")]
+    pub database: String,
+
+    /// Athena workgroup to run queries and prepared statements in
+    #[arg(short, long, default_value = "primary")]
+    pub workgroup: String,
+
+    /// S3 bucket for query results (the workgroup setting is used when omitted)
+    #[arg(long)]
+    pub output_bucket: Option<String>,
+
+    /// Reuse results of identical recent queries instead of scanning the data again
+    #[arg(long)]
+    pub reuse: bool,
+
+    /// Maximum age in minutes of a result that can be reused
+    #[arg(long, value_name = "MINUTES", default_value_t = 60, requires = "reuse")]
+    pub reuse_max_age: i32,
+
+    /// Execute a single statement and exit
+    #[arg(short, long)]
+    pub execute: Option<String>,
+
+    /// Run the statements in a SQL script file and exit
+    #[arg(short, long, value_name = "PATH", conflicts_with = "execute")]
+    pub file: Option<PathBuf>,
+
+    /// Maximum number of script statements running at once
+    #[arg(long, value_name = "N", default_value_t = 4)]
+    pub max_in_flight: usize,
+
+    /// Times a read-only query that failed transiently, e.g. on S3 throttling or an internal error, is resubmitted
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    pub query_retries: u32,
+
+    /// Run all script statements concurrently, not only those marked `-- @independent`
+    #[arg(long, requires = "file")]
+    pub parallel: bool,
+
+    /// Value bound to a `?` placeholder of --execute, in order (repeatable)
+    #[arg(short, long = "param", value_name = "VALUE")]
+    pub params: Vec<String>,
+
+    /// Set a variable available as ${name} in statements (repeatable)
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = vars::parse_assignment)]
+    pub vars: Vec<(String, String)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum BackendKind {
+    Athena, // Amazon Athena, using the AWS profile
+    Local,  // an offline engine over local CSV and Parquet files
+}
@@ -0,0 +1,165 @@
+use crate::format::print_table;
+use serde_json::Value;
+
+// Rewrite an EXPLAIN statement without options to request a JSON plan.
+// Returns None for other statements, or when the user already chose options such as FORMAT.
+pub fn rewrite_explain(query: &str) -> Option<String> {
+    let mut rest = query.trim_start();
+    let mut prefix: Vec<&str> = Vec::new();
+    for keyword in ["EXPLAIN", "ANALYZE", "VERBOSE"] {
+        let word = rest.split_whitespace().next()?;
+        if word.eq_ignore_ascii_case(keyword) {
+            prefix.push(keyword);
+            rest = rest[word.len()..].trim_start();
+        } else if keyword == "EXPLAIN" {
+            return None;
+        }
+    }
+    if rest.starts_with('(') {
+        return None;
+    }
+    Some(format!("{} (FORMAT JSON) {}", prefix.join(" "), rest))
+}
+
+// Print an EXPLAIN result as an operator tree, falling back to the raw rows when it is not a JSON plan
+pub fn print_plan(rows: &[Vec<String>]) {
+    // the first row is the "Query Plan" header, the plan itself may be split over several rows
+    let json = rows
+        .iter()
+        .skip(1)
+        .filter_map(|row| row.first())
+        .cloned()
+        .collect::<Vec<String>>()
+        .join("\n");
+    match serde_json::from_str::<Value>(&json) {
+        Ok(plan) => print!("{}", render_plan(&plan)),
+        Err(_) => print_table(rows),
+    }
+}
+
+pub fn render_plan(plan: &Value) -> String {
+    let mut output = String::new();
+    match plan.get("fragments").and_then(Value::as_array) {
+        // Athena: {"fragments": [{"id", "stageStats", "logicalPlan": {"<id>": [node]}}]}
+        Some(fragments) => {
+            for fragment in fragments {
+                output.push_str(&format!("Fragment {}", text(fragment.get("id"))));
+                if let Some(stats) = fragment.get("stageStats").filter(|s| !s.is_null()) {
+                    output.push_str(&format!(
+                        " [cpu: {}, input: {}, output: {}]",
+                        text(stats.get("totalCpuTime")),
+                        text(stats.get("inputRows")),
+                        text(stats.get("outputRows"))
+                    ));
+                }
+                output.push('\n');
+                for nodes in fragment_roots(fragment.get("logicalPlan")) {
+                    render_node(nodes, "", true, &mut output);
+                }
+            }
+        }
+        // Trino: {"<fragment id>": node} for distributed plans, or a single node for logical plans
+        None if plan.get("name").is_some() => render_node(plan, "", true, &mut output),
+        None => {
+            if let Some(fragments) = plan.as_object() {
+                for (id, node) in fragments {
+                    output.push_str(&format!("Fragment {}\n", id));
+                    render_node(node, "", true, &mut output);
+                }
+            }
+        }
+    }
+    output
+}
+
+fn fragment_roots(logical_plan: Option<&Value>) -> Vec<&Value> {
+    let Some(plan) = logical_plan.and_then(Value::as_object) else {
+        return Vec::new();
+    };
+    plan.values()
+        .flat_map(|nodes| match nodes.as_array() {
+            Some(nodes) => nodes.iter().collect::<Vec<&Value>>(),
+            None => vec![nodes],
+        })
+        .collect()
+}
+
+fn render_node(node: &Value, indent: &str, last: bool, output: &mut String) {
+    let branch = if last { "└─ " } else { "├─ " };
+    let child_indent = format!("{}{}", indent, if last { "   " } else { "│  " });
+
+    let mut line = format!("{}{}{}", indent, branch, text(node.get("name")));
+    let identifier = text(node.get("identifier").or_else(|| node.get("descriptor")));
+    if !identifier.is_empty() && identifier != "{}" {
+        line.push_str(&format!(" {}", identifier));
+    }
+    output.push_str(&line);
+    output.push('\n');
+
+    if let Some(estimate) = node
+        .get("estimates")
+        .and_then(Value::as_array)
+        .and_then(|e| e.first())
+    {
+        output.push_str(&format!(
+            "{}   est. rows: {}, cpu: {}, memory: {}, network: {}\n",
+            child_indent,
+            number(estimate.get("outputRowCount")),
+            number(estimate.get("cpuCost")),
+            number(estimate.get("memoryCost")),
+            number(estimate.get("networkCost"))
+        ));
+    }
+    // only present for EXPLAIN ANALYZE
+    if let Some(stats) = node.get("distributedNodeStats").filter(|s| !s.is_null()) {
+        output.push_str(&format!(
+            "{}   actual cpu: {}, input rows: {}, output rows: {}\n",
+            child_indent,
+            text(stats.get("nodeCpuTime")),
+            number(stats.get("nodeInputRows")),
+            number(stats.get("nodeOutputRows"))
+        ));
+    }
+
+    let children = node
+        .get("children")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for (i, child) in children.iter().enumerate() {
+        render_node(child, &child_indent, i + 1 == children.len(), output);
+    }
+}
+
+fn text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(Value::Object(map)) => map
+            .iter()
+            .map(|(k, v)| format!("{} = {}", k, text(Some(v))))
+            .collect::<Vec<String>>()
+            .join(", "),
+        Some(v) => v.to_string(),
+    }
+}
+
+// Compact numbers such as 12345678 as "12.3M"; Athena sends unknown estimates as "NaN"
+fn number(value: Option<&Value>) -> String {
+    let n = match value {
+        Some(Value::Number(n)) => n.as_f64(),
+        Some(Value::String(s)) => s.parse::<f64>().ok(),
+        _ => None,
+    };
+    match n {
+        Some(n) if n.is_finite() => {
+            let units = [(1e12, "T"), (1e9, "B"), (1e6, "M"), (1e3, "k")];
+            match units.iter().find(|(scale, _)| n.abs() >= *scale) {
+                Some((scale, suffix)) => format!("{:.1}{}", n / scale, suffix),
+                None if n.fract() == 0.0 => format!("{}", n as i64),
+                None => format!("{:.2}", n),
+            }
+        }
+        _ => "?".into(),
+    }
+}
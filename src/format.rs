@@ -0,0 +1,112 @@
+use aws_smithy_types::DateTime;
+use aws_smithy_types::date_time::Format;
+
+// Render a result set as an aligned text table.
+// The first row is treated as the header, matching what Athena returns for SELECT queries.
+pub fn print_table(rows: &[Vec<String>]) {
+    let Some((header, body)) = rows.split_first() else {
+        println!("(0 rows)");
+        return;
+    };
+
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in body {
+        for (i, cell) in row.iter().enumerate() {
+            let len = cell.chars().count();
+            match widths.get_mut(i) {
+                Some(w) => *w = (*w).max(len),
+                None => widths.push(len),
+            }
+        }
+    }
+
+    println!("{}", format_row(header, &widths));
+    println!(
+        "{}",
+        widths
+            .iter()
+            .map(|w| "-".repeat(w + 2))
+            .collect::<Vec<String>>()
+            .join("+")
+    );
+    for row in body {
+        println!("{}", format_row(row, &widths));
+    }
+
+    match body.len() {
+        1 => println!("(1 row)"),
+        n => println!("({} rows)", n),
+    }
+}
+
+fn format_row(row: &[String], widths: &[usize]) -> String {
+    widths
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            let cell = row.get(i).map(String::as_str).unwrap_or("");
+            format!(" {:<width$} ", cell, width = w)
+        })
+        .collect::<Vec<String>>()
+        .join("|")
+}
+
+// Format a byte count with a binary unit suffix, e.g. "1.5 GiB"
+pub fn format_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+// Format a catalog timestamp like 2024-07-03T09:46:40Z
+pub fn format_timestamp(time: &DateTime) -> String {
+    time.fmt(Format::DateTime).unwrap_or_default()
+}
+
+// Lines of the statement shown above the one a syntax error points at
+const ERROR_CONTEXT_LINES: usize = 2;
+
+// The submitted statement around the position a failure reason like
+// "line 3:15: mismatched input" points at, with a caret under the column
+pub fn highlight_error(query: &str, reason: &str) -> Option<String> {
+    let (line, column) = error_position(reason)?;
+    let lines: Vec<&str> = query.lines().collect();
+    let failing = *lines.get(line.checked_sub(1)?)?;
+    let gutter = line.to_string().len();
+    let mut highlighted = String::new();
+    for number in line.saturating_sub(ERROR_CONTEXT_LINES).max(1)..=line {
+        highlighted.push_str(&format!(
+            "{:>gutter$} | {}\n",
+            number,
+            lines[number - 1],
+            gutter = gutter
+        ));
+    }
+    // keep tabs in the padding so the caret lines up however they are displayed
+    let padding: String = failing
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    highlighted.push_str(&format!("{:>gutter$} | {}^", "", padding, gutter = gutter));
+    Some(highlighted)
+}
+
+// The 1-based line and column of the first "line L:C" in a failure reason
+fn error_position(reason: &str) -> Option<(usize, usize)> {
+    reason.match_indices("line ").find_map(|(i, _)| {
+        let rest = &reason[i + "line ".len()..];
+        let (line, rest) = rest.split_once(':')?;
+        let column: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        Some((line.parse().ok()?, column.parse().ok()?))
+    })
+}
@@ -0,0 +1,59 @@
+// Shell-style patterns for filtering catalog listings: * matches any run of characters,
+// ? any single character, and [abc], [a-z] or [!abc] one character of a set. Catalog,
+// database and table names are case-insensitive, so matching is too.
+
+pub fn is_pattern(text: &str) -> bool {
+    text.contains(['*', '?', '['])
+}
+
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    matches_from(&pattern, &text)
+}
+
+fn matches_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| matches_from(rest, &text[i..])),
+        Some(('?', rest)) => !text.is_empty() && matches_from(rest, &text[1..]),
+        Some(('[', rest)) => match (class_end(rest), text.split_first()) {
+            (Some(end), Some((c, text))) => {
+                class_matches(&rest[..end], *c) && matches_from(&rest[end + 1..], text)
+            }
+            (Some(_), None) => false,
+            // an unclosed [ is an ordinary character, like in the shell
+            (None, _) => text.first() == Some(&'[') && matches_from(rest, &text[1..]),
+        },
+        Some((p, rest)) => text.first() == Some(p) && matches_from(rest, &text[1..]),
+    }
+}
+
+// Position of the ] closing a set; a ] right after [ or [! belongs to the set
+fn class_end(class: &[char]) -> Option<usize> {
+    let start = if class.first() == Some(&'!') { 2 } else { 1 };
+    class
+        .iter()
+        .skip(start)
+        .position(|&c| c == ']')
+        .map(|i| i + start)
+}
+
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, set) = match class.split_first() {
+        Some(('!', set)) => (true, set),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < set.len() {
+        if i + 2 < set.len() && set[i + 1] == '-' {
+            found |= (set[i]..=set[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= set[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
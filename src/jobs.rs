@@ -0,0 +1,90 @@
+use crate::aws::error::{Result, ShellError};
+use crate::backend::QueryBackend;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+pub struct Job {
+    pub execution_id: String,
+    pub query: String,
+    pub handle: JoinHandle<Result<Vec<Vec<String>>>>,
+    pub notified: bool, // whether completion has been reported at the prompt
+}
+
+#[derive(Default)]
+pub struct Jobs {
+    next_id: usize,
+    jobs: BTreeMap<usize, Job>,
+}
+
+impl Jobs {
+    // Wait for an already submitted query in a background task, returning the job number
+    pub fn spawn(
+        &mut self,
+        backend: Arc<dyn QueryBackend>,
+        execution_id: String,
+        query: String,
+        poll_interval: u64,
+    ) -> usize {
+        let id = execution_id.clone();
+        let handle = tokio::spawn(async move {
+            let summary = backend.wait_for_query(&id, poll_interval).await?;
+            if summary.state != "SUCCEEDED" {
+                return Err(ShellError::QueryFailed {
+                    execution_id: id,
+                    reason: summary.failure_reason(),
+                });
+            }
+            backend.get_query_results(&id).await
+        });
+
+        self.next_id += 1;
+        self.jobs.insert(
+            self.next_id,
+            Job {
+                execution_id,
+                query,
+                handle,
+                notified: false,
+            },
+        );
+        self.next_id
+    }
+
+    // Resolve a job argument ("2" or "%2"), defaulting to the most recent job
+    pub fn resolve(&self, job: Option<&str>) -> Option<usize> {
+        match job {
+            Some(job) => job
+                .trim_start_matches('%')
+                .parse::<usize>()
+                .ok()
+                .filter(|id| self.jobs.contains_key(id)),
+            None => self.jobs.keys().next_back().copied(),
+        }
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.get_mut(&id)
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        self.jobs.remove(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&usize, &Job)> {
+        self.jobs.iter()
+    }
+
+    // Jobs that finished since the last check, marking them as reported
+    pub fn take_finished(&mut self) -> Vec<(usize, String)> {
+        self.jobs
+            .iter_mut()
+            .filter(|(_, job)| !job.notified && job.handle.is_finished())
+            .map(|(id, job)| {
+                job.notified = true;
+                (*id, job.query.clone())
+            })
+            .collect()
+    }
+}
@@ -1 +1,2 @@
-mod aws;
+pub mod aws;
+pub mod backend;
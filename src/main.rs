@@ -1,34 +1,215 @@
 mod aws;
+mod backend;
+mod cli;
+mod explain;
+mod format;
+mod glob;
+mod jobs;
+mod logging;
 mod meta;
+mod profile;
 mod repl;
+mod script;
+mod search;
+mod vars;
 
+use aws_config::{BehaviorVersion, Region};
+use aws_credential_types::Credentials;
+use clap::Parser;
 use inquire::{Select, Text};
 use std::error::Error;
+use std::sync::Arc;
+use tracing::info;
 
-use crate::aws::config::{self, build_config};
+use crate::aws::athena::QueryContext;
+use crate::aws::cassette::{Cassette, Traffic};
+use crate::aws::config;
+use crate::aws::endpoint::Endpoints;
+use crate::aws::sts;
+use crate::backend::local::LocalBackend;
+use crate::backend::{Backend, RetryPolicy};
+use crate::cli::{BackendKind, Cli};
+
+// Seconds between status checks of local or replayed statements, which need no timeout prompt
+const OFFLINE_POLL_INTERVAL: u64 = 1;
+// Region of a replayed session when --region is not given; the cassette answers either way
+const REPLAY_REGION: &str = "us-east-1";
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let credential_file_path = aws::config::get_credentials_path()?;
+    let cli = Cli::parse();
+    logging::init(cli.verbose, cli.quiet, cli.log_file.as_deref())?;
 
-    // Load AWS profile and build Service Config
-    let avail_profile = config::get_aws_profile(&credential_file_path)?;
-    let selected_profile = Select::new("AWS profile name to use:", avail_profile).prompt()?;
-    let input_timeout = Text::new("Put timeout value:").prompt()?;
+    let traffic = match (&cli.record, &cli.replay) {
+        (Some(path), _) => Traffic::Record(Arc::new(Cassette::create(path)?)),
+        (_, Some(path)) => Traffic::Replay(Arc::new(Cassette::open(path)?)),
+        _ => Traffic::Live,
+    };
 
-    println!(
-        "\nUsing profile: {} to build SDK config...",
-        selected_profile
-    );
+    // Load AWS profile and build Service Config, which local and replayed sessions do not need
+    let offline = cli.backend == BackendKind::Local || traffic.is_replay();
+    let selected_profile = match offline {
+        true => cli.profile,
+        false => select_profile(cli.profile)?,
+    };
+    let timeout = match cli.timeout {
+        Some(timeout) => timeout,
+        None if offline => OFFLINE_POLL_INTERVAL,
+        None => {
+            let input_timeout = Text::new("Put timeout value:").prompt()?;
+            str::parse::<u64>(input_timeout.as_str())?
+        }
+    };
 
-    let timeout = str::parse::<u64>(input_timeout.as_str())?;
     let no_stall_protection = true;
-    let service_config = build_config(&selected_profile, timeout, no_stall_protection).await?;
+    let (service_config, mfa_source, backend) = match cli.backend {
+        BackendKind::Local => {
+            let local_backend = LocalBackend::open(&cli.local_dir).await?;
+            let service_config = aws_types::SdkConfig::builder()
+                .behavior_version(BehaviorVersion::latest())
+                .build();
+            (
+                service_config,
+                None,
+                Backend::Local(Arc::new(local_backend)),
+            )
+        }
+        BackendKind::Athena if traffic.is_replay() => {
+            // requests are still signed, so the session gets placeholder credentials
+            let region = cli.region.clone().unwrap_or(REPLAY_REGION.to_string());
+            let service_config = aws_config::defaults(BehaviorVersion::latest())
+                .region(Region::new(region))
+                .credentials_provider(Credentials::new("replay", "replay", None, None, "replay"))
+                .load()
+                .await;
+            (service_config, None, Backend::Athena)
+        }
+        BackendKind::Athena => {
+            match &selected_profile {
+                Some(profile) => {
+                    println!("\nUsing profile: {} to build SDK config...", profile);
+                    info!(profile, "building the SDK config");
+                }
+                None => {
+                    println!("\nNo profile selected, using the default credential chain...");
+                    info!("no profile selected, building the SDK config from the default chain")
+                }
+            }
+            // The SDK cannot prompt for an MFA token, so profiles that need one are assumed here
+            let (service_config, mfa_source) = sts::build_session_config(
+                selected_profile.as_deref(),
+                cli.region.as_deref(),
+                timeout,
+                no_stall_protection,
+                async |serial: &str| {
+                    Ok(Text::new(&format!("MFA token code for {}:", serial)).prompt()?)
+                },
+            )
+            .await?;
+            (service_config, mfa_source, Backend::Athena)
+        }
+    };
+    let endpoints = Endpoints {
+        athena: cli.athena_endpoint_url,
+        glue: cli.glue_endpoint_url,
+        s3: cli.s3_endpoint_url,
+        s3_force_path_style: cli.s3_force_path_style,
+    };
+    let service_config = traffic.apply(&endpoints.apply(&service_config));
+    match &traffic {
+        Traffic::Record(cassette) => println!("Recording to {}", cassette.path().display()),
+        Traffic::Replay(cassette) => println!("Replaying {}", cassette.path().display()),
+        Traffic::Live => {}
+    }
+
+    let ctx = QueryContext {
+        database: cli.database,
+        workgroup: cli.workgroup,
+        output_bucket: cli.output_bucket,
+        result_reuse_max_age: cli.reuse.then_some(cli.reuse_max_age),
+    };
+    let mut repl = repl::Repl::new(
+        selected_profile.as_deref(),
+        cli.region.as_deref(),
+        ctx.clone(),
+        timeout,
+        no_stall_protection,
+    );
+    repl.set_mfa_source(mfa_source);
+    repl.set_endpoints(endpoints);
+    repl.set_traffic(traffic);
+    repl.set_backend(backend.clone());
+    let retry = RetryPolicy {
+        max_retries: cli.query_retries,
+    };
+    repl.set_retry_policy(retry);
+    for (name, value) in cli.vars {
+        repl.set_var(name, value);
+    }
+
+    // Run a single statement non-interactively
+    if let Some(query) = cli.execute {
+        let query = query.trim().trim_end_matches(';');
+        if let Err(e) = repl
+            .run_statement(query, &cli.params, &service_config)
+            .await
+        {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
+    // Run a script file non-interactively
+    if let Some(path) = cli.file {
+        let script = std::fs::read_to_string(&path)?;
+        let mut statements = script::parse_script(&script);
+        for statement in statements.iter_mut() {
+            statement.query = repl.interpolate(&statement.query)?;
+        }
+        let options = script::ScriptOptions {
+            max_in_flight: cli.max_in_flight,
+            all_independent: cli.parallel,
+            poll_interval: timeout,
+            retry,
+        };
+        let backend = backend.open(&service_config).await?;
+        let succeeded = script::run_script(backend, &ctx, statements, &options).await?;
+        std::process::exit(if succeeded { 0 } else { 1 });
+    }
 
     // Run the REPL
-    let mut repl = repl::Repl::new(&selected_profile);
     repl.repl_loop(service_config).await?;
 
     // Force Tokio runtime termination to return immediately to OS shell
     std::process::exit(0);
 }
+
+// Pick the profile from --profile, then AWS_PROFILE, then the picker when profile files exist.
+// None means the default credential chain (environment, web identity, container, instance metadata).
+fn select_profile(cli_profile: Option<String>) -> Result<Option<String>, Box<dyn Error>> {
+    if let Some(profile) = cli_profile {
+        return Ok(Some(profile));
+    }
+    if let Ok(profile) = std::env::var("AWS_PROFILE")
+        && !profile.is_empty()
+    {
+        return Ok(Some(profile));
+    }
+    // credentials in the environment take precedence over asking for a profile
+    if std::env::var_os("AWS_ACCESS_KEY_ID").is_some() {
+        return Ok(None);
+    }
+    let credential_file_path = config::get_credentials_path()?;
+    let config_file_path = config::get_config_path()?;
+    if !credential_file_path.exists() && !config_file_path.exists() {
+        return Ok(None);
+    }
+    let avail_profile = config::get_aws_profile(&credential_file_path, &config_file_path)?;
+    if avail_profile.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(
+        Select::new("AWS profile name to use:", avail_profile).prompt()?,
+    ))
+}
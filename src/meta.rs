@@ -1,20 +1,277 @@
 #![allow(unused)]
 
-use crate::aws::{athena::AthenaService, glue::GlueService};
+use crate::aws::{
+    athena::{AthenaService, DEFAULT_CATALOG, QueryContext},
+    error::Result,
+    glue::GlueService,
+};
+use crate::backend::Backend;
+use crate::format::{format_timestamp, print_table};
+use crate::glob;
+use crate::vars;
+
+// Athena's default maximum age for reused query results, in minutes
+const DEFAULT_REUSE_MAX_AGE: i32 = 60;
 
 #[derive(Debug, PartialEq)]
 pub enum MetaCommand {
-    Quit,                  // "\q"
-    Help,                  // "\h"
-    ListCatalogs,          // "\lc"
-    ListDatabases(String), // "\ld <catalog_name> - catalog name as parameter"
-    ListTables(String),    // "\lt <database_name> - database name as parameter"
+    Quit,                         // "\q"
+    Help,                         // "\h"
+    ListCatalogs(Listing),        // "\lc[+] [pattern]", "+" adding each catalog's type
+    ListDatabases(Listing),       // "\ld[+] [catalog_name] [pattern]", "+" adding descriptions
+    ListTables(Listing),          // "\lt[+] [database_name] [pattern]", "+" adding Glue details
+    Bind(Vec<String>),            // "\bind [values...] - parameters for the next statement"
+    ListPrepared,                 // "\prepare"
+    Prepare(String, String),      // "\prepare <name> <sql>"
+    Execute(String, Vec<String>), // "\execute <name> [values...]"
+    Deallocate(String),           // "\deallocate <name>"
+    Set(String, String),          // "\set <name> [value]"
+    Unset(String),                // "\unset <name>"
+    ListVariables,                // "\vars" or "\set" without arguments
+    Jobs,                         // "\jobs"
+    Wait(Option<String>),         // "\wait [job]" or "\fg [job]"
+    Kill(String),                 // "\kill <job>"
+    Reuse(Option<Option<i32>>),   // "\reuse [on [max-age] | off]", no argument shows the setting
+    Explain(bool, String),        // "\explain [analyze] <sql>"
+    Profile(Option<String>),      // "\profile [execution_id]"
+    Assume(String),               // "\assume <role_arn>"
+    Connect(Option<String>),      // "\connect [profile]", no argument opens the profile picker
+    Region(String),               // "\region <name>"
+    Search(String),               // "\search <term> - tables by name, description or column"
+    // "\dp[+] <database>.<table> [expression]", "+" adding the number of files of each partition
+    ListPartitions(String, String, Option<String>, bool),
+}
+
+// Arguments of "\lc", "\ld" and "\lt"
+#[derive(Debug, PartialEq)]
+pub struct Listing {
+    pub scope: Option<String>, // catalog of "\ld" or database of "\lt", the default when None
+    pub pattern: Option<String>, // glob the listed names must match
+    pub detailed: bool,        // the "+" variant
+}
+
+impl MetaCommand {
+    // The Athena-only feature a command uses, if any
+    pub fn athena_feature(&self) -> Option<&'static str> {
+        match self {
+            MetaCommand::ListPrepared
+            | MetaCommand::Prepare(_, _)
+            | MetaCommand::Execute(_, _)
+            | MetaCommand::Deallocate(_) => Some("Prepared statements"),
+            MetaCommand::Profile(_) => Some("Query runtime statistics"),
+            MetaCommand::ListCatalogs(Listing { detailed: true, .. })
+            | MetaCommand::ListDatabases(Listing { detailed: true, .. })
+            | MetaCommand::ListTables(Listing { detailed: true, .. }) => Some("Detailed listings"),
+            MetaCommand::ListPartitions(_, _, _, _) => Some("Partition listing"),
+            MetaCommand::Search(_) => Some("Catalog search"),
+            MetaCommand::Assume(_) | MetaCommand::Connect(_) | MetaCommand::Region(_) => {
+                Some("Switching credentials")
+            }
+            _ => None,
+        }
+    }
+
+    // Whether the command can run again after failing on expired credentials. "\wait" has
+    // already taken the job's result and "\assume" would reuse a spent MFA code.
+    pub fn is_replayable(&self) -> bool {
+        !matches!(self, MetaCommand::Wait(_) | MetaCommand::Assume(_))
+    }
+
+    // Parse a line starting with a backslash, returning the usage text when the arguments are invalid
+    pub fn parse(input: &str) -> std::result::Result<MetaCommand, String> {
+        let input = input.trim();
+        let (cmd, rest) = match input.split_once(char::is_whitespace) {
+            Some((cmd, rest)) => (cmd, rest.trim()),
+            None => (input, ""),
+        };
+        let args = split_args(rest);
+        let detailed = cmd.ends_with('+');
+
+        match cmd {
+            "\\q" => Ok(MetaCommand::Quit),
+            "\\h" => Ok(MetaCommand::Help),
+            "\\lc" | "\\lc+" => match args.as_slice() {
+                [] | [_] => Ok(MetaCommand::ListCatalogs(Listing {
+                    scope: None,
+                    pattern: args.first().cloned(),
+                    detailed,
+                })),
+                _ => Err("Usage: \\lc[+] [pattern]".into()),
+            },
+            "\\ld" | "\\ld+" => match parse_listing(&args, detailed) {
+                Some(listing) => Ok(MetaCommand::ListDatabases(listing)),
+                None => Err("Usage: \\ld[+] [catalog_name] [pattern]".into()),
+            },
+            "\\lt" | "\\lt+" => match parse_listing(&args, detailed) {
+                Some(listing) => Ok(MetaCommand::ListTables(listing)),
+                None => Err("Usage: \\lt[+] [database_name] [pattern]".into()),
+            },
+            "\\dp" | "\\dp+" => {
+                let rest = rest.trim_end_matches(';').trim_end();
+                let (name, expression) = match rest.split_once(char::is_whitespace) {
+                    Some((name, expression)) => (name, Some(expression.trim().to_string())),
+                    None => (rest, None),
+                };
+                match name.split_once('.') {
+                    Some((database, table)) if !database.is_empty() && !table.is_empty() => {
+                        Ok(MetaCommand::ListPartitions(
+                            database.into(),
+                            table.into(),
+                            expression,
+                            detailed,
+                        ))
+                    }
+                    _ => Err("Usage: \\dp[+] <database>.<table> [expression]".into()),
+                }
+            }
+            "\\search" => match rest.trim_end_matches(';').trim_end() {
+                "" => Err("Usage: \\search <term>".into()),
+                term => Ok(MetaCommand::Search(term.into())),
+            },
+            "\\bind" => Ok(MetaCommand::Bind(args)),
+            "\\prepare" => {
+                if rest.is_empty() {
+                    return Ok(MetaCommand::ListPrepared);
+                }
+                match rest.split_once(char::is_whitespace) {
+                    Some((name, sql)) => Ok(MetaCommand::Prepare(
+                        name.into(),
+                        sql.trim().trim_end_matches(';').trim_end().into(),
+                    )),
+                    None => Err("Usage: \\prepare <name> <sql>".into()),
+                }
+            }
+            "\\execute" => match args.split_first() {
+                Some((name, values)) => Ok(MetaCommand::Execute(name.clone(), values.to_vec())),
+                None => Err("Usage: \\execute <name> [values...]".into()),
+            },
+            "\\deallocate" => match args.as_slice() {
+                [name] => Ok(MetaCommand::Deallocate(name.clone())),
+                _ => Err("Usage: \\deallocate <name>".into()),
+            },
+            "\\set" => {
+                if rest.is_empty() {
+                    return Ok(MetaCommand::ListVariables);
+                }
+                let (name, value) = match rest.split_once(char::is_whitespace) {
+                    Some((name, value)) => (name, vars::unquote(value.trim())),
+                    None => (rest, String::new()),
+                };
+                if !vars::is_valid_name(name) {
+                    return Err(format!("Invalid variable name: {}", name));
+                }
+                Ok(MetaCommand::Set(name.into(), value))
+            }
+            "\\unset" => match args.as_slice() {
+                [name] => Ok(MetaCommand::Unset(name.clone())),
+                _ => Err("Usage: \\unset <name>".into()),
+            },
+            "\\vars" => Ok(MetaCommand::ListVariables),
+            "\\jobs" => Ok(MetaCommand::Jobs),
+            "\\wait" | "\\fg" => match args.as_slice() {
+                [] => Ok(MetaCommand::Wait(None)),
+                [job] => Ok(MetaCommand::Wait(Some(job.clone()))),
+                _ => Err(format!("Usage: {} [job]", cmd)),
+            },
+            "\\kill" => match args.as_slice() {
+                [job] => Ok(MetaCommand::Kill(job.clone())),
+                _ => Err("Usage: \\kill <job>".into()),
+            },
+            "\\reuse" => match args.as_slice() {
+                [] => Ok(MetaCommand::Reuse(None)),
+                [off] if off == "off" => Ok(MetaCommand::Reuse(Some(None))),
+                [on] if on == "on" => Ok(MetaCommand::Reuse(Some(Some(DEFAULT_REUSE_MAX_AGE)))),
+                [on, max_age] if on == "on" => match max_age.parse::<i32>() {
+                    Ok(max_age) if max_age > 0 => Ok(MetaCommand::Reuse(Some(Some(max_age)))),
+                    _ => Err(format!("Invalid max age in minutes: {}", max_age)),
+                },
+                _ => Err("Usage: \\reuse [on [max-age-minutes] | off]".into()),
+            },
+            "\\explain" => {
+                let sql = rest.trim_end_matches(';').trim_end();
+                match sql.split_once(char::is_whitespace) {
+                    Some((word, sql)) if word.eq_ignore_ascii_case("analyze") => {
+                        Ok(MetaCommand::Explain(true, sql.trim().into()))
+                    }
+                    _ if !sql.is_empty() && !sql.eq_ignore_ascii_case("analyze") => {
+                        Ok(MetaCommand::Explain(false, sql.into()))
+                    }
+                    _ => Err("Usage: \\explain [analyze] <sql>".into()),
+                }
+            }
+            "\\profile" => match args.as_slice() {
+                [] => Ok(MetaCommand::Profile(None)),
+                [execution_id] => Ok(MetaCommand::Profile(Some(execution_id.clone()))),
+                _ => Err("Usage: \\profile [execution_id]".into()),
+            },
+            "\\assume" => match args.as_slice() {
+                [role_arn] => Ok(MetaCommand::Assume(role_arn.clone())),
+                _ => Err("Usage: \\assume <role_arn>".into()),
+            },
+            "\\connect" => match args.as_slice() {
+                [] => Ok(MetaCommand::Connect(None)),
+                [profile] => Ok(MetaCommand::Connect(Some(profile.clone()))),
+                _ => Err("Usage: \\connect [profile]".into()),
+            },
+            "\\region" => match args.as_slice() {
+                [name] => Ok(MetaCommand::Region(name.clone())),
+                _ => Err("Usage: \\region <name>".into()),
+            },
+            _ => Err(format!("Unknown command: {} (type '\\h' for help)", cmd)),
+        }
+    }
+}
+
+// "[scope] [pattern]" of "\ld" and "\lt"; a single argument is the pattern when it has
+// wildcards, otherwise what to list
+fn parse_listing(args: &[String], detailed: bool) -> Option<Listing> {
+    let (scope, pattern) = match args {
+        [] => (None, None),
+        [pattern] if glob::is_pattern(pattern) => (None, Some(pattern.clone())),
+        [scope] => (Some(scope.clone()), None),
+        [scope, pattern] => (Some(scope.clone()), Some(pattern.clone())),
+        _ => return None,
+    };
+    Some(Listing {
+        scope,
+        pattern,
+        detailed,
+    })
+}
+
+// Split meta command arguments on whitespace, keeping single-quoted SQL literals
+// (including their quotes) together so they can be passed on as execution parameters
+pub fn split_args(input: &str) -> Vec<String> {
+    let mut args: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in input.chars() {
+        match c {
+            '\'' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    args
 }
 
 pub async fn execute_meta_command(
     cmd: MetaCommand,
     service: &aws_types::SdkConfig,
-) -> Result<(), Box<dyn std::error::Error>> {
+    ctx: &QueryContext,
+    backend: &Backend,
+) -> Result<()> {
     match cmd {
         MetaCommand::Help => {
             println!(
@@ -25,10 +282,64 @@ pub async fn execute_meta_command(
 ╚═══════════════════════════════════════╝
 
 Meta Commands:
-    \h                 Show this help message
-    \q                 Exit the shell
-    \lc                List available catalogs
-    \ld <catalog_name> List available databases under catalog
+    \h                          Show this help message
+    \q                          Exit the shell
+    \lc [pattern]               List available catalogs
+    \ld [catalog] [pattern]     List available databases under catalog (AwsDataCatalog)
+    \lt [database] [pattern]    List available tables under database (the current one)
+    \lc+, \ld+, \lt+            Also show details, e.g. the type, location, owner,
+                                last update and column count of tables
+    \dp <db>.<table> [expr]     List the partitions of a table, only those matching a
+                                Glue filter expression when given, e.g. year = '2024'
+    \dp+ <db>.<table> [expr]    Also count the files of each partition
+    \search <term>              Find tables whose name, description, columns or
+                                column comments mention a term, in all databases
+
+    Patterns are shell-style globs, e.g. \lt sales_* or \ld AwsDataCatalog *_raw
+
+Parameterized Queries:
+    \bind [values...]           Bind values to the ? placeholders of the next statement
+    \prepare                    List prepared statements in the workgroup
+    \prepare <name> <sql>       Create (or replace) a prepared statement
+    \execute <name> [values...] Run a prepared statement with the given values
+    \deallocate <name>          Delete a prepared statement
+
+    Values are SQL literals, e.g. \execute by_day '2024-01-01' 10
+
+Variables:
+    \set <name> [value]         Set a variable (without arguments, list variables)
+    \unset <name>               Remove a variable
+    \vars                       List variables
+    <sql> \gset [prefix]        Run a single-row query and store each column
+                                in a variable named [prefix]<column>
+
+    ${{name}} is replaced by the value, ${{'name'}} by the value as a string
+    literal and ${{"name"}} by the value as a quoted identifier
+
+Background Jobs:
+    <sql>; &  or  <sql> \bg     Run a statement in the background
+    \jobs                       List background jobs and their state
+    \wait [job], \fg [job]      Wait for a job and show its result
+    \kill <job>                 Stop a background job
+
+Query Result Reuse:
+    \reuse                      Show whether query results are reused
+    \reuse on [max-age]         Reuse results up to max-age minutes old (default 60)
+    \reuse off                  Always run queries against the data
+
+Query Plans:
+    \explain <sql>              Show the query plan as an operator tree
+    \explain analyze <sql>      Run the query and show actual per-operator statistics
+
+    \profile [execution_id]     Show runtime statistics of the last (or given) query
+
+    EXPLAIN statements without options are shown the same way
+
+Session:
+    \connect [profile]          Switch to another AWS profile (without a name, pick one)
+    \region <name>              Switch to another AWS region
+    \assume <role_arn>          Switch the session to a role, asking for an MFA
+                                token code when the profile requires one
 
 Query Commands:
     End statements with semicolon (;) to execute
@@ -43,24 +354,180 @@ Controls:
         MetaCommand::Quit => {
             println!("Exiting Shell!")
         }
-        MetaCommand::ListCatalogs => {
-            println!("Listing Catalogues");
+        MetaCommand::ListCatalogs(Listing {
+            pattern, detailed, ..
+        }) => {
+            let mut rows = match detailed {
+                true => AthenaService::new(service)
+                    .await?
+                    .list_catalogs_with_types()
+                    .await?
+                    .into_iter()
+                    .map(|(name, catalog_type)| vec![name, catalog_type])
+                    .collect(),
+                false => backend
+                    .open(service)
+                    .await?
+                    .list_catalogs()
+                    .await?
+                    .into_iter()
+                    .map(|name| vec![name])
+                    .collect(),
+            };
+            print_listing(
+                &mut rows,
+                pattern.as_deref(),
+                detailed,
+                &["catalog", "type"],
+            );
+        }
+        MetaCommand::ListDatabases(Listing {
+            scope,
+            pattern,
+            detailed,
+        }) => {
+            let catalog_name = scope.unwrap_or_else(|| DEFAULT_CATALOG.to_string());
+            let mut rows = match detailed {
+                true => AthenaService::new(service)
+                    .await?
+                    .list_databases_with_descriptions(&catalog_name)
+                    .await?
+                    .into_iter()
+                    .map(|(name, description)| vec![name, description.unwrap_or_default()])
+                    .collect(),
+                false => backend
+                    .open(service)
+                    .await?
+                    .list_databases(&catalog_name)
+                    .await?
+                    .into_iter()
+                    .map(|name| vec![name])
+                    .collect(),
+            };
+            print_listing(
+                &mut rows,
+                pattern.as_deref(),
+                detailed,
+                &["database", "description"],
+            );
+        }
+        MetaCommand::ListTables(Listing {
+            scope,
+            pattern,
+            detailed,
+        }) => {
+            let database_name = scope.unwrap_or_else(|| ctx.database.clone());
+            let mut rows = match detailed {
+                true => GlueService::new(service)
+                    .await?
+                    .get_tables(&database_name)
+                    .await?
+                    .into_iter()
+                    .map(|table| {
+                        vec![
+                            table.name,
+                            table.table_type.unwrap_or_default(),
+                            table.location.unwrap_or_default(),
+                            table.owner.unwrap_or_default(),
+                            table
+                                .update_time
+                                .as_ref()
+                                .map(format_timestamp)
+                                .unwrap_or_default(),
+                            table.columns.len().to_string(),
+                        ]
+                    })
+                    .collect(),
+                false => backend
+                    .open(service)
+                    .await?
+                    .list_tables(&database_name)
+                    .await?
+                    .into_iter()
+                    .map(|name| vec![name])
+                    .collect(),
+            };
+            print_listing(
+                &mut rows,
+                pattern.as_deref(),
+                detailed,
+                &["table", "type", "location", "owner", "updated", "columns"],
+            );
+        }
+        MetaCommand::ListPrepared => {
+            println!(
+                "Listing Prepared Statements for workgroup: {}",
+                ctx.workgroup
+            );
             let athena_service: AthenaService = AthenaService::new(service).await?;
-            let cat = athena_service.list_catalogs().await?;
-            println!("{:?}", cat);
+            let mut rows: Vec<Vec<String>> = athena_service
+                .list_prepared_statements(&ctx.workgroup)
+                .await?
+                .into_iter()
+                .map(|name| vec![name])
+                .collect();
+            print_listing(&mut rows, None, false, &["Statement"]);
         }
-        MetaCommand::ListDatabases(catalog_name) => {
-            println!("Listing Databases for catalog: {}", catalog_name);
+        MetaCommand::Prepare(name, sql) => {
             let athena_service: AthenaService = AthenaService::new(service).await?;
-            let dbs = athena_service.list_databases(&catalog_name).await?;
-            println!("{:?}", dbs);
+            // CreatePreparedStatement fails on an existing name, so replace it like `PREPARE` does,
+            // updating it in place so that a failure leaves the old statement intact
+            if athena_service
+                .list_prepared_statements(&ctx.workgroup)
+                .await?
+                .contains(&name)
+            {
+                athena_service
+                    .update_prepared_statement(&ctx.workgroup, &name, &sql)
+                    .await?;
+            } else {
+                athena_service
+                    .create_prepared_statement(&ctx.workgroup, &name, &sql)
+                    .await?;
+            }
+            println!("Prepared statement: {}", name);
         }
-        MetaCommand::ListTables(database_name) => {
-            println!("Listing Tables for Database: {}", database_name);
-            let glue_service = GlueService::new(service).await?;
-            let tables = glue_service.list_tables(&database_name).await?;
-            println!("{:?}", tables);
+        MetaCommand::Deallocate(name) => {
+            let athena_service: AthenaService = AthenaService::new(service).await?;
+            athena_service
+                .delete_prepared_statement(&ctx.workgroup, &name)
+                .await?;
+            println!("Deallocated prepared statement: {}", name);
         }
+        // handled by the REPL, which owns the session state and runs the query
+        MetaCommand::Bind(_)
+        | MetaCommand::Execute(_, _)
+        | MetaCommand::Set(_, _)
+        | MetaCommand::Unset(_)
+        | MetaCommand::ListVariables
+        | MetaCommand::Jobs
+        | MetaCommand::Wait(_)
+        | MetaCommand::Kill(_)
+        | MetaCommand::Reuse(_)
+        | MetaCommand::Explain(_, _)
+        | MetaCommand::Profile(_)
+        | MetaCommand::Assume(_)
+        | MetaCommand::Connect(_)
+        | MetaCommand::Region(_)
+        | MetaCommand::Search(_)
+        | MetaCommand::ListPartitions(_, _, _, _) => {}
     }
     Ok(())
 }
+
+// Print the rows of a listing whose first column, the name, matches pattern, sorted by name.
+// header names the columns of the detailed listing, of which a plain one only has the first.
+fn print_listing(
+    rows: &mut Vec<Vec<String>>,
+    pattern: Option<&str>,
+    detailed: bool,
+    header: &[&str],
+) {
+    if let Some(pattern) = pattern {
+        rows.retain(|row| glob::matches(pattern, &row[0]));
+    }
+    rows.sort();
+    let header = if detailed { header } else { &header[..1] };
+    rows.insert(0, header.iter().map(|h| h.to_string()).collect());
+    print_table(rows);
+}
@@ -0,0 +1,146 @@
+use crate::format::{format_bytes, print_table};
+use aws_sdk_athena::types::{QueryRuntimeStatistics, QueryStage, QueryStagePlanNode};
+
+// Share of the total stage time or input data above which a stage is flagged as dominant
+const HOT_STAGE_SHARE: f64 = 0.3;
+
+pub fn print_profile(execution_id: &str, stats: &QueryRuntimeStatistics) {
+    println!("Runtime statistics for query: {}", execution_id);
+
+    if let Some(timeline) = stats.timeline() {
+        let phases = [
+            ("queued", timeline.query_queue_time_in_millis()),
+            (
+                "pre-processing",
+                timeline.service_pre_processing_time_in_millis(),
+            ),
+            ("planning", timeline.query_planning_time_in_millis()),
+            ("execution", timeline.engine_execution_time_in_millis()),
+            (
+                "post-processing",
+                timeline.service_processing_time_in_millis(),
+            ),
+            ("total", timeline.total_execution_time_in_millis()),
+        ];
+        let line = phases
+            .iter()
+            .filter_map(|(name, ms)| ms.map(|ms| format!("{} {}", name, format_millis(ms))))
+            .collect::<Vec<String>>()
+            .join(" | ");
+        println!("Timeline: {}", line);
+    }
+    if let Some(rows) = stats.rows() {
+        println!(
+            "Rows: input {} ({}), output {} ({})",
+            count(rows.input_rows()),
+            bytes(rows.input_bytes()),
+            count(rows.output_rows()),
+            bytes(rows.output_bytes())
+        );
+    }
+
+    let Some(output_stage) = stats.output_stage() else {
+        return;
+    };
+    let mut stages: Vec<&QueryStage> = Vec::new();
+    flatten_stages(output_stage, &mut stages);
+    stages.sort_by_key(|s| s.stage_id());
+
+    let total_time: i64 = stages.iter().filter_map(|s| s.execution_time()).sum();
+    let total_input: i64 = stages.iter().filter_map(|s| s.input_bytes()).sum();
+
+    println!();
+    let header = [
+        "stage",
+        "state",
+        "time",
+        "rows in",
+        "bytes in",
+        "rows out",
+        "bytes out",
+        "hot",
+    ];
+    let mut rows = vec![header.map(String::from).to_vec()];
+    for stage in &stages {
+        let mut hot: Vec<&str> = Vec::new();
+        if is_dominant(stage.execution_time(), total_time) {
+            hot.push("time");
+        }
+        if is_dominant(stage.input_bytes(), total_input) {
+            hot.push("data");
+        }
+        rows.push(vec![
+            count(stage.stage_id()),
+            stage.state().unwrap_or_default().to_string(),
+            stage
+                .execution_time()
+                .map(format_millis)
+                .unwrap_or_default(),
+            count(stage.input_rows()),
+            bytes(stage.input_bytes()),
+            count(stage.output_rows()),
+            bytes(stage.output_bytes()),
+            hot.join(", "),
+        ]);
+    }
+    print_table(&rows);
+
+    for stage in &stages {
+        if let Some(plan) = stage.query_stage_plan() {
+            println!("\nStage {} operators:", count(stage.stage_id()));
+            let mut output = String::new();
+            render_plan_node(plan, "", true, &mut output);
+            print!("{}", output);
+        }
+    }
+}
+
+fn flatten_stages<'a>(stage: &'a QueryStage, stages: &mut Vec<&'a QueryStage>) {
+    stages.push(stage);
+    for sub_stage in stage.sub_stages() {
+        flatten_stages(sub_stage, stages);
+    }
+}
+
+fn is_dominant(value: Option<i64>, total: i64) -> bool {
+    match value {
+        Some(value) if total > 0 => value as f64 / total as f64 >= HOT_STAGE_SHARE,
+        _ => false,
+    }
+}
+
+fn render_plan_node(node: &QueryStagePlanNode, indent: &str, last: bool, output: &mut String) {
+    let branch = if last { "└─ " } else { "├─ " };
+    let child_indent = format!("{}{}", indent, if last { "   " } else { "│  " });
+
+    let mut line = format!("{}{}{}", indent, branch, node.name().unwrap_or("?"));
+    if let Some(identifier) = node.identifier().filter(|i| !i.is_empty()) {
+        line.push_str(&format!(" {}", identifier));
+    }
+    if !node.remote_sources().is_empty() {
+        line.push_str(&format!(" <- stage {}", node.remote_sources().join(", ")));
+    }
+    output.push_str(&line);
+    output.push('\n');
+
+    let children = node.children();
+    for (i, child) in children.iter().enumerate() {
+        render_plan_node(child, &child_indent, i + 1 == children.len(), output);
+    }
+}
+
+fn format_millis(ms: i64) -> String {
+    if ms >= 1000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        format!("{}ms", ms)
+    }
+}
+
+fn count(value: Option<i64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn bytes(value: Option<i64>) -> String {
+    value.map(format_bytes).unwrap_or_default()
+}
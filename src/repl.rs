@@ -1,26 +1,119 @@
-use crate::aws::error::Result;
+use crate::aws::athena::{AthenaService, QueryContext, QuerySummary};
+use crate::aws::cassette::Traffic;
+use crate::aws::config;
+use crate::aws::endpoint::Endpoints;
+use crate::aws::error::{Result, ShellError};
+use crate::aws::glue::GlueService;
+use crate::aws::s3::{S3Service, parse_s3_uri};
+use crate::aws::sts::{self, MfaSource};
+use crate::backend::{Backend, RetryPolicy};
+use crate::explain;
+use crate::format::{format_bytes, format_timestamp, highlight_error, print_table};
+use crate::jobs::Jobs;
 use crate::meta::{MetaCommand, execute_meta_command};
+use crate::profile;
+use crate::search;
+use crate::vars::{self, Variables};
 
+use inquire::Select;
 use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::AsyncBufReadExt;
 use tokio::signal;
-use tokio::sync::mpsc;
+use tokio::sync::{Semaphore, mpsc};
+use tokio::task::JoinSet;
+use tracing::{Span, field, info, instrument};
+
+// How many partitions "\dp+" counts the S3 objects of at the same time
+const PARTITION_COUNT_CONCURRENCY: usize = 8;
 
 pub struct Repl {
-    prompt: String,         // prompt chars
-    input_buf: Vec<String>, // buffer to accumulate stdin input
-    multiline: bool,        // state management of the input
+    prompt: String,                    // prompt chars
+    profile: Option<String>,           // profile of the session, None for the default chain
+    region: Option<String>,            // region overriding the profile's, set with "\region"
+    no_stall_protection: bool,         // passed to build_config when rebuilding the config
+    endpoints: Endpoints,              // endpoint URLs applied when rebuilding the config
+    traffic: Traffic,                  // cassette recording or replaying the AWS requests
+    backend: Backend,                  // where statements run, Athena or the local engine
+    input_buf: Vec<String>,            // buffer to accumulate stdin input
+    multiline: bool,                   // state management of the input
+    ctx: QueryContext,                 // database, workgroup and output location for queries
+    timeout: u64,                      // seconds to wait between query status checks
+    retry: RetryPolicy,                // resubmission of transiently failed statements
+    bind_params: Vec<String>,          // values bound with "\bind" for the next statement
+    vars: Variables,                   // client-side variables substituted into statements
+    jobs: Jobs,                        // statements running in the background
+    last_execution_id: Option<String>, // most recent foreground query, used by "\profile"
+    // statement submitted by the current command, polled again rather than resubmitted
+    // when the command is retried with renewed credentials
+    in_flight: Option<String>,
+    assumed_role: Option<String>, // role switched to with "\assume"
+    // credentials and MFA device that "\assume" uses when the profile requires MFA
+    mfa_source: Option<MfaSource>,
 }
 
 impl Repl {
-    pub fn new(profile: &str) -> Self {
+    pub fn new(
+        profile: Option<&str>,
+        region: Option<&str>,
+        ctx: QueryContext,
+        timeout: u64,
+        no_stall_protection: bool,
+    ) -> Self {
         Repl {
-            prompt: format!("{}> ", profile),
+            prompt: make_prompt(profile, region),
+            profile: profile.map(String::from),
+            region: region.map(String::from),
+            no_stall_protection,
+            endpoints: Endpoints::default(),
+            traffic: Traffic::Live,
+            backend: Backend::Athena,
             input_buf: Vec::new(),
             multiline: false,
+            ctx,
+            timeout,
+            retry: RetryPolicy::default(),
+            bind_params: Vec::new(),
+            vars: Variables::new(),
+            jobs: Jobs::default(),
+            last_execution_id: None,
+            in_flight: None,
+            assumed_role: None,
+            mfa_source: None,
         }
     }
 
+    // Assume roles from these credentials with an MFA token, since roles assumed
+    // from the session's own temporary credentials do not carry MFA
+    pub fn set_mfa_source(&mut self, mfa_source: Option<MfaSource>) {
+        self.mfa_source = mfa_source;
+    }
+
+    pub fn set_endpoints(&mut self, endpoints: Endpoints) {
+        self.endpoints = endpoints;
+    }
+
+    pub fn set_traffic(&mut self, traffic: Traffic) {
+        self.traffic = traffic;
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    pub fn set_retry_policy(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
+
+    pub fn set_var(&mut self, name: String, value: String) {
+        self.vars.insert(name, value);
+    }
+
+    pub fn interpolate(&self, input: &str) -> Result<String> {
+        vars::interpolate(input, &self.vars)
+    }
+
     pub fn print_header(&self) {
         println!(
             r#"
@@ -36,40 +129,23 @@ Type '\q' to exit from shell
         )
     }
 
-    pub async fn repl_loop(&mut self, service_config: aws_types::SdkConfig) -> Result<()> {
+    pub async fn repl_loop(&mut self, mut service_config: aws_types::SdkConfig) -> Result<()> {
         // Print header when first time entering the shell
         self.print_header();
 
-        // Create a channel for delivering lines from stdin task
-        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
-
         // Create a dedicated stdin reader task
-        tokio::spawn(async move {
-            let stdin = tokio::io::stdin();
-            let mut reader = tokio::io::BufReader::new(stdin);
-            let mut line = String::new();
-
-            // Check for stdin input from keyboard
-            // SIGNAL is not treated as a valid stdin input from keyboard
-            loop {
-                line.clear();
-                match reader.read_line(&mut line).await {
-                    Ok(0) => break, // EOF -> 0 bytes read
-                    Ok(_) => {
-                        if tx.send(line.clone()).is_err() {
-                            break;
-                        }
-                    }
-                    Err(_) => break,
-                }
-            }
-        });
+        let mut stdin = Input::spawn();
 
         // By default the shell is not in multiline mode
         self.multiline = false;
 
         // Begin REPL loop
         loop {
+            // Report background jobs that finished while the prompt was idle
+            for (id, query) in self.jobs.take_finished() {
+                println!("[{}] Done    {}", id, query);
+            }
+
             // If the buffer is processing multi-line input, change the prompt into "|"
             if self.multiline {
                 print!("| ");
@@ -91,79 +167,63 @@ Type '\q' to exit from shell
                         println!("\n(Use Ctrl-D to exit)");
                     }
                 }
-                result = rx.recv() => {
+                result = stdin.next_line() => {
                     match result {
                         Some(line) => {
-                            if !self.multiline {
-                                if line.trim().is_empty() { // handle case where user just press Enter (empty input)
-                                    continue;
-                                }
-                                match line.trim() {
-                                    "\\h" => {
-                                        let meta = MetaCommand::Help;
-                                        let _ = execute_meta_command(meta, &service_config).await;
-                                        continue
-                                    }
-                                    "\\q" => {
-                                        let meta = MetaCommand::Quit;
-                                        let _ = execute_meta_command(meta, &service_config).await;
-                                        return Ok(());
-                                    }
-                                    "\\lc"=> {
-                                        let meta = MetaCommand::ListCatalogs;
-                                        match execute_meta_command(meta, &service_config).await {
-                                            Ok(_) => println!("Command executed successfully"),
-                                            Err(e) => println!("Error: {}", e)
-                                        }
-                                        continue
-                                    }
-                                    input if input.starts_with("\\ld ") => {
-                                        let parts: Vec<&str> = line.trim().split_whitespace().collect();
-                                        if parts.len() != 2 {
-                                            println!("Usage: \\ld <catalog_name>");
-                                            continue;
+                            if !self.multiline && line.trim().is_empty() { // handle case where user just press Enter (empty input)
+                                continue;
+                            }
+                            self.in_flight = None;
+                            let terminated = split_terminator(&line);
+                            // a lone "\gset" or "\bg" ends the buffered statement rather than being a meta command
+                            let is_meta = !self.multiline
+                                && line.trim_start().starts_with('\\')
+                                && !matches!(terminated, Some(("", _)));
+                            if is_meta {
+                                let command = line.trim();
+                                let config = &mut service_config;
+                                let mut result = self.handle_meta(command, config, &mut stdin).await;
+                                // retry once with renewed credentials when they had expired, unless
+                                // the steps that did succeed must not run again
+                                if self.renew_if_expired(&result, config, &mut stdin).await {
+                                    match MetaCommand::parse(command).is_ok_and(|meta| meta.is_replayable()) {
+                                        true => {
+                                            println!("Retrying");
+                                            result = self.handle_meta(command, config, &mut stdin).await;
                                         }
-                                        let catalog_name = parts[1].to_string();
-                                        let meta = MetaCommand::ListDatabases(catalog_name);
-                                        let _ = execute_meta_command(meta, &service_config).await;
-                                        continue
+                                        false => println!("Run the command again to retry it"),
                                     }
-                                    input if input.starts_with("\\lt ") => {
-                                        let parts: Vec<&str> = line.trim().split_whitespace().collect();
-                                        if parts.len() != 2 {
-                                            println!("Usage:: \\lt <database_name>");
-                                            continue;
-                                        }
-                                        let database_name = parts[1].to_string();
-                                        let meta = MetaCommand::ListTables(database_name);
-                                        let _ = execute_meta_command(meta, &service_config).await;
-                                        continue;
+                                }
+                                match result {
+                                    Ok(true) => return Ok(()),
+                                    Ok(false) => {}
+                                    Err(e) => println!("Error: {}", e),
+                                }
+                                continue;
+                            }
+                            match terminated {
+                                Some((head, terminator)) => {
+                                    self.input_buf.push(head.to_string());
+                                    let command = self.take_buffer();
+                                    self.multiline = false;
+                                    let params = std::mem::take(&mut self.bind_params);
+                                    let mut result = self
+                                        .run_terminated(&command, terminator, &params, &service_config)
+                                        .await;
+                                    // a statement that was already submitted is only polled again
+                                    if self.renew_if_expired(&result, &mut service_config, &mut stdin).await {
+                                        println!("Retrying");
+                                        result = self
+                                            .run_terminated(&command, terminator, &params, &service_config)
+                                            .await;
                                     }
-                                    _ => {
-                                        if line.trim_end().ends_with(';') {
-                                            let command = String::from(line.trim());
-                                            println!("Input command: {}", command); // placeholder for actual work
-                                        } else {
-                                            self.multiline = true;
-                                            self.input_buf.push(line);
-                                        }
+                                    if let Err(e) = result {
+                                        println!("Error: {}", e);
                                     }
                                 }
-                            } else {
-                                self.input_buf.push(line.clone());
-                                if line.trim_end().ends_with(';') {
-                                    let command = self
-                                        .input_buf
-                                        .iter()
-                                        .map(|s| s.replace('\n', "").trim().to_string())
-                                        .filter(|s| !s.is_empty())
-                                        .collect::<Vec<String>>()
-                                        .join(" ")
-                                        .replace(" ;", ";");
-
-                                    println!("Multiline command: {}", command);
-                                    self.multiline = false;
-                                    self.input_buf.clear();
+                                None => {
+                                    self.multiline = true;
+                                    self.input_buf.push(line);
                                 }
                             }
                         }
@@ -177,4 +237,774 @@ Type '\q' to exit from shell
             }
         }
     }
+
+    // Join the buffered input lines into a single statement and clear the buffer
+    fn take_buffer(&mut self) -> String {
+        // keep the line breaks, so Athena reports error positions against the lines as typed
+        let command = self
+            .input_buf
+            .iter()
+            .map(|s| s.trim_end_matches(['\r', '\n']))
+            .collect::<Vec<&str>>()
+            .join("\n")
+            .trim()
+            .to_string();
+        self.input_buf.clear();
+        command
+    }
+
+    // Dispatch a meta command, returning true when the shell should exit
+    async fn handle_meta(
+        &mut self,
+        input: &str,
+        service_config: &mut aws_types::SdkConfig,
+        stdin: &mut Input,
+    ) -> Result<bool> {
+        let input = vars::interpolate(input, &self.vars)?;
+        let meta = match MetaCommand::parse(&input) {
+            Ok(meta) => meta,
+            Err(usage) => {
+                println!("{}", usage);
+                return Ok(false);
+            }
+        };
+        if let Some(feature) = meta.athena_feature()
+            && self.backend.is_local()
+        {
+            return Err(ShellError::UnsupportedByBackend(feature.into()));
+        }
+        // a replayed session has no credentials to switch from
+        if matches!(
+            meta,
+            MetaCommand::Assume(_) | MetaCommand::Connect(_) | MetaCommand::Region(_)
+        ) && self.traffic.is_replay()
+        {
+            return Err(ShellError::UnsupportedInReplay(
+                "Switching credentials".into(),
+            ));
+        }
+        match meta {
+            MetaCommand::Quit => {
+                let _ = execute_meta_command(meta, service_config, &self.ctx, &self.backend).await;
+                return Ok(true);
+            }
+            MetaCommand::Bind(values) => {
+                println!("Bound {} parameter(s) for the next statement", values.len());
+                self.bind_params = values;
+            }
+            MetaCommand::Set(name, value) => {
+                self.vars.insert(name, value);
+            }
+            MetaCommand::Unset(name) => {
+                if self.vars.remove(&name).is_none() {
+                    println!("Variable not set: {}", name);
+                }
+            }
+            MetaCommand::ListVariables => {
+                let mut rows = vec![vec!["name".to_string(), "value".to_string()]];
+                rows.extend(self.vars.iter().map(|(k, v)| vec![k.clone(), v.clone()]));
+                print_table(&rows);
+            }
+            MetaCommand::Reuse(setting) => {
+                if let Some(max_age) = setting {
+                    self.ctx.result_reuse_max_age = max_age;
+                }
+                match self.ctx.result_reuse_max_age {
+                    Some(max_age) => println!("Result reuse is on (max age {} minutes)", max_age),
+                    None => println!("Result reuse is off"),
+                }
+            }
+            MetaCommand::Profile(execution_id) => {
+                match execution_id.or_else(|| self.last_execution_id.clone()) {
+                    Some(execution_id) => self.show_profile(&execution_id, service_config).await?,
+                    None => println!("No query has been run yet, pass an execution ID"),
+                }
+            }
+            MetaCommand::ListPartitions(database, table, expression, file_counts) => {
+                self.list_partitions(
+                    &database,
+                    &table,
+                    expression.as_deref(),
+                    file_counts,
+                    service_config,
+                )
+                .await?;
+            }
+            MetaCommand::Search(term) => {
+                // the cache fallback keeps one catalog per profile, region and assumed role
+                let cache_key = format!(
+                    "{}_{}_{}",
+                    self.profile.as_deref().unwrap_or("default"),
+                    service_config
+                        .region()
+                        .map(|r| r.as_ref())
+                        .unwrap_or("default"),
+                    self.assumed_role.as_deref().unwrap_or("none")
+                );
+                search::search_catalog(service_config, &term, &cache_key).await?;
+            }
+            MetaCommand::Assume(role_arn) => {
+                *service_config = self.assume_role(&role_arn, service_config, stdin).await?;
+                println!("Assumed role: {}", role_arn);
+                self.assumed_role = Some(role_arn);
+            }
+            MetaCommand::Connect(name) => {
+                let profile = match name {
+                    Some(name) => name,
+                    None => {
+                        let profiles = config::get_aws_profile(
+                            &config::get_credentials_path()?,
+                            &config::get_config_path()?,
+                        )?;
+                        Select::new("AWS profile name to use:", profiles).prompt()?
+                    }
+                };
+                self.switch_session(Some(profile), self.region.clone(), service_config, stdin)
+                    .await?;
+            }
+            MetaCommand::Region(region) => {
+                self.switch_session(self.profile.clone(), Some(region), service_config, stdin)
+                    .await?;
+            }
+            MetaCommand::Jobs => self.list_jobs(service_config).await?,
+            MetaCommand::Wait(job) => self.wait_job(job.as_deref()).await?,
+            MetaCommand::Kill(job) => self.kill_job(&job, service_config).await?,
+            MetaCommand::Explain(analyze, sql) => {
+                let query = match analyze {
+                    true => format!("EXPLAIN ANALYZE {}", sql),
+                    false => format!("EXPLAIN {}", sql),
+                };
+                // handle_meta already substituted the variables of the whole line
+                self.run_interpolated(&query, &[], service_config).await?;
+            }
+            MetaCommand::Execute(name, values) => {
+                self.run_interpolated(&format!("EXECUTE {}", name), &values, service_config)
+                    .await?;
+            }
+            meta => execute_meta_command(meta, service_config, &self.ctx, &self.backend).await?,
+        }
+        Ok(false)
+    }
+
+    // Offer to renew the session's credentials when a command failed because they expired,
+    // returning true when they were renewed and the command can be retried
+    async fn renew_if_expired<T>(
+        &mut self,
+        result: &Result<T>,
+        service_config: &mut aws_types::SdkConfig,
+        stdin: &mut Input,
+    ) -> bool {
+        if !result.as_ref().is_err_and(|e| e.is_expired_credentials()) || self.traffic.is_replay() {
+            return false;
+        }
+        let sso_profile = self
+            .profile
+            .clone()
+            .filter(|profile| config::is_sso_profile(profile).unwrap_or(false));
+        println!("The credentials of this session have expired.");
+        let question = match sso_profile {
+            Some(_) => "Run \"aws sso login\" [l], reload credentials [r] or skip [Enter]: ",
+            None => "Reload credentials [r] or skip [Enter]: ",
+        };
+        match (read_line(question, stdin).await.as_str(), &sso_profile) {
+            ("l", Some(profile)) => {
+                let status = tokio::process::Command::new("aws")
+                    .args(["sso", "login", "--profile", profile])
+                    .status()
+                    .await;
+                match status {
+                    Ok(status) if status.success() => {}
+                    Ok(status) => {
+                        println!("aws sso login failed: {}", status);
+                        return false;
+                    }
+                    Err(e) => {
+                        println!("Could not run aws sso login: {}", e);
+                        return false;
+                    }
+                }
+            }
+            ("r", _) => {}
+            _ => return false,
+        }
+        match self.reconnect(service_config, stdin).await {
+            Ok(()) => {
+                println!("Credentials renewed");
+                true
+            }
+            Err(e) => {
+                println!("Could not renew credentials: {}", e);
+                false
+            }
+        }
+    }
+
+    // Rebuild the SDK config from the profile, re-reading the credentials files,
+    // and switch back to a role assumed with "\assume"
+    async fn reconnect(
+        &mut self,
+        service_config: &mut aws_types::SdkConfig,
+        stdin: &mut Input,
+    ) -> Result<()> {
+        let (config, mfa_source) = sts::build_session_config(
+            self.profile.as_deref(),
+            self.region.as_deref(),
+            self.timeout,
+            self.no_stall_protection,
+            async |serial: &str| {
+                Ok(read_line(&format!("MFA token code for {}: ", serial), stdin).await)
+            },
+        )
+        .await?;
+        self.mfa_source = mfa_source;
+        *service_config = self.traffic.apply(&self.endpoints.apply(&config));
+        if let Some(role_arn) = self.assumed_role.clone() {
+            *service_config = self.assume_role(&role_arn, service_config, stdin).await?;
+        }
+        Ok(())
+    }
+
+    // Rebuild the session for another profile or region, keeping variables, bound parameters
+    // and background jobs. The current session is kept when the new one cannot be built.
+    async fn switch_session(
+        &mut self,
+        profile: Option<String>,
+        region: Option<String>,
+        service_config: &mut aws_types::SdkConfig,
+        stdin: &mut Input,
+    ) -> Result<()> {
+        let previous = (
+            std::mem::replace(&mut self.profile, profile),
+            std::mem::replace(&mut self.region, region),
+            self.assumed_role.take(),
+            self.mfa_source.take(),
+        );
+        // a role assumed with "\assume" belongs to the previous account, only keep it for a new region
+        if self.profile == previous.0 {
+            self.assumed_role = previous.2.clone();
+        }
+        if let Err(e) = self.reconnect(service_config, stdin).await {
+            (
+                self.profile,
+                self.region,
+                self.assumed_role,
+                self.mfa_source,
+            ) = previous;
+            return Err(e);
+        }
+        self.prompt = make_prompt(self.profile.as_deref(), self.region.as_deref());
+        Ok(())
+    }
+
+    // Run a terminated statement with the parameters bound for it with "\bind"
+    async fn run_terminated(
+        &mut self,
+        command: &str,
+        terminator: Terminator<'_>,
+        params: &[String],
+        service_config: &aws_types::SdkConfig,
+    ) -> Result<()> {
+        if command.is_empty() {
+            println!("Query buffer is empty");
+            return Ok(());
+        }
+        match terminator {
+            Terminator::Semicolon => {
+                let query = command.trim_end_matches(';').trim_end();
+                self.run_statement(query, params, service_config).await
+            }
+            Terminator::Gset(prefix) => {
+                self.run_gset(command, prefix, params, service_config).await
+            }
+            Terminator::Background => self.run_background(command, params, service_config).await,
+        }
+    }
+
+    // Run a statement that must return exactly one row and store each column in a variable
+    async fn run_gset(
+        &mut self,
+        command: &str,
+        prefix: Option<&str>,
+        params: &[String],
+        service_config: &aws_types::SdkConfig,
+    ) -> Result<()> {
+        let query = vars::interpolate(command.trim_end_matches(';').trim_end(), &self.vars)?;
+        let (_, rows) = self
+            .execute_statement(&query, params, service_config)
+            .await?;
+
+        let (header, values) = match rows.as_slice() {
+            [header, values] => (header, values),
+            [] | [_] => return Err(ShellError::NoRowsReturned),
+            [_, body @ ..] => return Err(ShellError::TooManyRowsReturned(body.len())),
+        };
+        let assignments = header
+            .iter()
+            .zip(values)
+            .map(|(column, value)| {
+                let name = format!("{}{}", prefix.unwrap_or(""), column);
+                if vars::is_valid_name(&name) {
+                    Ok((name, value.clone()))
+                } else {
+                    Err(ShellError::InvalidVariableName(name))
+                }
+            })
+            .collect::<Result<Vec<(String, String)>>>()?;
+        for (name, value) in assignments {
+            self.vars.insert(name, value);
+        }
+        Ok(())
+    }
+
+    // Submit a statement and wait for it in a background task so the prompt stays responsive
+    async fn run_background(
+        &mut self,
+        command: &str,
+        params: &[String],
+        service_config: &aws_types::SdkConfig,
+    ) -> Result<()> {
+        let query = vars::interpolate(command.trim_end_matches(';').trim_end(), &self.vars)?;
+        let backend = self.backend.open(service_config).await?;
+        let execution_id = backend
+            .submit_query(&self.ctx, &query, params, &report_saturated(&self.ctx))
+            .await?;
+        let id = self
+            .jobs
+            .spawn(backend, execution_id.clone(), query, self.timeout);
+        println!("[{}] {}", id, execution_id);
+        Ok(())
+    }
+
+    // Assume a role for the rest of the session, reading the MFA token code from the input lines
+    async fn assume_role(
+        &self,
+        role_arn: &str,
+        service_config: &aws_types::SdkConfig,
+        stdin: &mut Input,
+    ) -> Result<aws_types::SdkConfig> {
+        let (source_config, mfa_serial) = match &self.mfa_source {
+            Some((source_config, mfa_serial)) => (source_config, Some(mfa_serial.as_str())),
+            None => (service_config, None),
+        };
+        let credentials = sts::assume_role_cached(
+            source_config,
+            role_arn,
+            mfa_serial,
+            None,
+            None,
+            async |serial: &str| {
+                Ok(read_line(&format!("MFA token code for {}: ", serial), stdin).await)
+            },
+        )
+        .await?;
+        Ok(sts::with_credentials(service_config, credentials))
+    }
+
+    async fn show_profile(
+        &self,
+        execution_id: &str,
+        service_config: &aws_types::SdkConfig,
+    ) -> Result<()> {
+        let athena_service = AthenaService::new(service_config).await?;
+        let stats = athena_service
+            .get_query_runtime_statistics(execution_id)
+            .await?;
+        profile::print_profile(execution_id, &stats);
+        Ok(())
+    }
+
+    // Show the partitions of a Glue table sorted by their values, with the number of files
+    // under each partition's location when file_counts is set
+    async fn list_partitions(
+        &self,
+        database: &str,
+        table: &str,
+        expression: Option<&str>,
+        file_counts: bool,
+        service_config: &aws_types::SdkConfig,
+    ) -> Result<()> {
+        let glue = GlueService::new(service_config).await?;
+        let mut header = glue.partition_keys(database, table).await?;
+        let mut partitions = glue.list_partitions(database, table, expression).await?;
+        partitions
+            .sort_by(|a, b| partition_sort_key(&a.values).cmp(&partition_sort_key(&b.values)));
+        let mut files: Vec<Option<usize>> = vec![None; partitions.len()];
+        if file_counts {
+            let s3 =
+                Arc::new(S3Service::new(service_config, self.endpoints.s3_force_path_style).await?);
+            let semaphore = Arc::new(Semaphore::new(PARTITION_COUNT_CONCURRENCY));
+            let mut tasks = JoinSet::new();
+            for (index, partition) in partitions.iter().enumerate() {
+                let Some((bucket, prefix)) = partition.location.as_deref().and_then(parse_s3_uri)
+                else {
+                    continue;
+                };
+                // the trailing slash keeps year=2024 from matching year=20241 too
+                let prefix = match prefix.trim_end_matches('/') {
+                    "" => String::new(),
+                    prefix => format!("{}/", prefix),
+                };
+                let (s3, semaphore, bucket) = (s3.clone(), semaphore.clone(), bucket.to_string());
+                tasks.spawn(async move {
+                    let _permit = semaphore.acquire_owned().await;
+                    (index, s3.count_objects(&bucket, &prefix).await)
+                });
+            }
+            while let Some(task) = tasks.join_next().await {
+                // a panicked count leaves its partition's cell empty
+                if let Ok((index, count)) = task {
+                    files[index] = Some(count?);
+                }
+            }
+        }
+
+        header.extend(["location".to_string(), "created".to_string()]);
+        if file_counts {
+            header.push("files".to_string());
+        }
+        let mut rows = vec![header];
+        for (partition, files) in partitions.into_iter().zip(files) {
+            let mut row = partition.values;
+            row.push(partition.location.clone().unwrap_or_default());
+            row.push(
+                partition
+                    .creation_time
+                    .as_ref()
+                    .map(format_timestamp)
+                    .unwrap_or_default(),
+            );
+            if file_counts {
+                row.push(files.map(|n| n.to_string()).unwrap_or_default());
+            }
+            rows.push(row);
+        }
+        print_table(&rows);
+        Ok(())
+    }
+
+    async fn list_jobs(&self, service_config: &aws_types::SdkConfig) -> Result<()> {
+        let backend = self.backend.open(service_config).await?;
+        let mut rows = vec![vec![
+            "job".to_string(),
+            "execution_id".to_string(),
+            "state".to_string(),
+            "query".to_string(),
+        ]];
+        for (id, job) in self.jobs.iter() {
+            let state = backend.get_query_state(&job.execution_id).await?;
+            rows.push(vec![
+                id.to_string(),
+                job.execution_id.clone(),
+                state,
+                job.query.clone(),
+            ]);
+        }
+        print_table(&rows);
+        Ok(())
+    }
+
+    // Bring a job to the foreground and print its result; Ctrl-C leaves it running
+    async fn wait_job(&mut self, job: Option<&str>) -> Result<()> {
+        let Some(id) = self.jobs.resolve(job) else {
+            println!("No such job: {}", job.unwrap_or("(none running)"));
+            return Ok(());
+        };
+        let Some(handle) = self.jobs.get_mut(id).map(|job| &mut job.handle) else {
+            return Ok(());
+        };
+        tokio::select! {
+            result = handle => {
+                self.jobs.remove(id);
+                match result {
+                    Ok(rows) => print_table(&rows?),
+                    Err(e) => println!("Job [{}] did not complete: {}", id, e),
+                }
+            }
+            _ = signal::ctrl_c() => {
+                println!("\nJob [{}] left running in the background", id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn kill_job(&mut self, job: &str, service_config: &aws_types::SdkConfig) -> Result<()> {
+        let Some(id) = self.jobs.resolve(Some(job)) else {
+            println!("No such job: {}", job);
+            return Ok(());
+        };
+        // the job stays in the table until the query is stopped, so a failed \kill can be repeated
+        let Some(execution_id) = self.jobs.get_mut(id).map(|job| job.execution_id.clone()) else {
+            return Ok(());
+        };
+        let backend = self.backend.open(service_config).await?;
+        backend.stop_query(&execution_id).await?;
+        if let Some(job) = self.jobs.remove(id) {
+            job.handle.abort();
+        }
+        println!("Stopped query: {}", execution_id);
+        Ok(())
+    }
+
+    pub async fn run_statement(
+        &mut self,
+        query: &str,
+        params: &[String],
+        service_config: &aws_types::SdkConfig,
+    ) -> Result<()> {
+        let query = vars::interpolate(query, &self.vars)?;
+        self.run_interpolated(&query, params, service_config).await
+    }
+
+    // Run a statement whose variables were already substituted. Substituting again would expand
+    // `${...}` inside the substituted values, even within the literals `${'name'}` quotes.
+    async fn run_interpolated(
+        &mut self,
+        query: &str,
+        params: &[String],
+        service_config: &aws_types::SdkConfig,
+    ) -> Result<()> {
+        // the local engine has its own EXPLAIN output, which is shown as is
+        let explain = match self.backend {
+            Backend::Athena => explain::rewrite_explain(query),
+            Backend::Local(_) => None,
+        };
+        let (summary, rows) = self
+            .execute_statement(explain.as_deref().unwrap_or(query), params, service_config)
+            .await?;
+        match explain {
+            Some(_) => explain::print_plan(&rows),
+            None => print_table(&rows),
+        }
+        print_summary(&summary);
+        Ok(())
+    }
+
+    // Submit a statement, wait for it to finish and fetch its result set
+    #[instrument(name = "statement", skip_all, fields(query = %query, execution_id = field::Empty))]
+    async fn execute_statement(
+        &mut self,
+        query: &str,
+        params: &[String],
+        service_config: &aws_types::SdkConfig,
+    ) -> Result<(QuerySummary, Vec<Vec<String>>)> {
+        let backend = self.backend.open(service_config).await?;
+        let mut retries = 0;
+        let execution_id = loop {
+            let execution_id = match self.in_flight.clone() {
+                Some(execution_id) => execution_id,
+                None => {
+                    backend
+                        .submit_query(&self.ctx, query, params, &report_saturated(&self.ctx))
+                        .await?
+                }
+            };
+            Span::current().record("execution_id", &execution_id);
+            self.last_execution_id = Some(execution_id.clone());
+            self.in_flight = Some(execution_id.clone());
+            // Ctrl-C stops the query rather than leaving it running unseen
+            let summary = tokio::select! {
+                summary = backend.wait_for_query(&execution_id, self.timeout) => summary?,
+                _ = signal::ctrl_c() => {
+                    self.in_flight = None;
+                    backend.stop_query(&execution_id).await?;
+                    info!("statement cancelled");
+                    return Err(ShellError::QueryCancelled { execution_id });
+                }
+            };
+            if summary.state == "SUCCEEDED" {
+                break execution_id;
+            }
+            self.in_flight = None;
+            if self.retry.should_retry(query, &summary, retries) {
+                let delay = self.retry.backoff(retries);
+                retries += 1;
+                println!(
+                    "Query {} failed transiently: {}",
+                    execution_id,
+                    summary.failure_reason().unwrap_or_default()
+                );
+                println!(
+                    "Resubmitting in {}s (retry {} of {})",
+                    delay.as_secs(),
+                    retries,
+                    self.retry.max_retries
+                );
+                info!(retries, "resubmitting a transiently failed statement");
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+            if let Some(reason) = summary.failure_reason() {
+                println!("{}", reason);
+                if let Some(highlighted) = highlight_error(query, &reason) {
+                    println!("{}", highlighted);
+                }
+            }
+            info!("statement failed");
+            return Err(ShellError::QueryFailed {
+                execution_id,
+                reason: None,
+            });
+        };
+        let rows = backend.get_query_results(&execution_id).await?;
+        let summary = backend.get_query_summary(&execution_id).await?;
+        self.in_flight = None;
+        info!(
+            rows = rows.len().saturating_sub(1),
+            execution_time_ms = summary.execution_time_ms,
+            "statement succeeded"
+        );
+        Ok((summary, rows))
+    }
+}
+
+// Prompt showing the profile, and the region when it was chosen explicitly
+fn make_prompt(profile: Option<&str>, region: Option<&str>) -> String {
+    match region {
+        Some(region) => format!("{}:{}> ", profile.unwrap_or("athena"), region),
+        None => format!("{}> ", profile.unwrap_or("athena")),
+    }
+}
+
+// Lines read from stdin by a dedicated task. A line is only read when one is requested,
+// so nothing competes with an inquire prompt for the terminal in between.
+struct Input {
+    requests: mpsc::UnboundedSender<()>,
+    lines: mpsc::UnboundedReceiver<String>,
+    pending: bool, // a line was requested and has not been received yet
+}
+
+impl Input {
+    fn spawn() -> Self {
+        let (request_tx, mut request_rx) = mpsc::unbounded_channel::<()>();
+        let (tx, rx) = mpsc::unbounded_channel::<String>();
+        tokio::spawn(async move {
+            let stdin = tokio::io::stdin();
+            let mut reader = tokio::io::BufReader::new(stdin);
+            let mut line = String::new();
+
+            // Check for stdin input from keyboard
+            // SIGNAL is not treated as a valid stdin input from keyboard
+            while request_rx.recv().await.is_some() {
+                line.clear();
+                match reader.read_line(&mut line).await {
+                    Ok(0) => break, // EOF -> 0 bytes read
+                    Ok(_) => {
+                        if tx.send(line.clone()).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        Input {
+            requests: request_tx,
+            lines: rx,
+            pending: false,
+        }
+    }
+
+    // Wait for the next line, None on EOF. Dropping the future (e.g. on Ctrl-C)
+    // keeps the request pending, so the line is picked up by the next call.
+    async fn next_line(&mut self) -> Option<String> {
+        if !self.pending {
+            self.pending = true;
+            let _ = self.requests.send(());
+        }
+        let line = self.lines.recv().await;
+        self.pending = false;
+        line
+    }
+}
+
+// Ask a question at the prompt and read the answer from the input lines
+async fn read_line(question: &str, stdin: &mut Input) -> String {
+    print!("{}", question);
+    let _ = std::io::stdout().flush();
+    stdin
+        .next_line()
+        .await
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+// Order partitions value by value, integers numerically so that month=2 comes before
+// month=10, and ahead of any other value, which sorts as text
+fn partition_sort_key(values: &[String]) -> Vec<(bool, Option<i128>, &str)> {
+    values
+        .iter()
+        .map(|value| {
+            let number = value.parse::<i128>().ok();
+            (number.is_none(), number, value.as_str())
+        })
+        .collect()
+}
+
+// Tell the user why a submission is waiting while the workgroup throttles it
+fn report_saturated(ctx: &QueryContext) -> impl Fn(Duration) + Send + Sync + '_ {
+    move |delay| {
+        println!(
+            "Workgroup {} is saturated, retrying in {:.1}s",
+            ctx.workgroup,
+            delay.as_secs_f64()
+        )
+    }
+}
+
+// One-line summary printed after a query's result set
+fn print_summary(summary: &QuerySummary) {
+    let mut line = format!("Query {} {}", summary.execution_id, summary.state);
+    if let Some(ms) = summary.execution_time_ms {
+        line.push_str(&format!(" in {:.1}s", ms as f64 / 1000.0));
+    }
+    if let Some(bytes) = summary.data_scanned_bytes {
+        line.push_str(&format!(", scanned {}", format_bytes(bytes)));
+    }
+    if summary.reused_previous_result {
+        line.push_str(" (reused previous result)");
+    }
+    println!("{}", line);
+}
+
+// How a statement was terminated, which decides how it is run
+#[derive(Clone, Copy)]
+enum Terminator<'a> {
+    Semicolon,             // "<sql>;"
+    Gset(Option<&'a str>), // "<sql> \gset [prefix]"
+    Background,            // "<sql>; &" or "<sql> \bg"
+}
+
+// Split a line that ends a statement into the statement text before the terminator and its kind
+fn split_terminator(line: &str) -> Option<(&str, Terminator<'_>)> {
+    let line = line.trim_end();
+    if let Some((head, prefix)) = split_gset(line) {
+        return Some((head, Terminator::Gset(prefix)));
+    }
+    if let Some(head) = line.strip_suffix("\\bg").or_else(|| line.strip_suffix('&')) {
+        return Some((
+            head.trim_end().trim_end_matches(';'),
+            Terminator::Background,
+        ));
+    }
+    line.strip_suffix(';')
+        .map(|head| (head, Terminator::Semicolon))
+}
+
+// Split a line ending with "\gset [prefix]" into the statement text before it and the prefix
+fn split_gset(line: &str) -> Option<(&str, Option<&str>)> {
+    let line = line.trim_end();
+    let pos = line.rfind("\\gset")?;
+    let (head, tail) = line.split_at(pos);
+    let args = &tail["\\gset".len()..];
+    // "\gsetx" is not a terminator
+    if !args.is_empty() && !args.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let prefix = match args.split_whitespace().collect::<Vec<&str>>().as_slice() {
+        [] => None,
+        [prefix] => Some(*prefix),
+        _ => return None,
+    };
+    Some((head.trim_end().trim_end_matches(';'), prefix))
 }
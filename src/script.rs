@@ -0,0 +1,288 @@
+use crate::aws::athena::QueryContext;
+use crate::aws::error::Result;
+use crate::backend::{QueryBackend, RetryPolicy};
+use crate::format::{format_bytes, highlight_error, print_table};
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{Span, field, info, instrument};
+
+// Comment marking a statement that may run concurrently with its marked neighbours
+const INDEPENDENT_MARKER: &str = "@independent";
+
+pub struct ScriptStatement {
+    pub query: String,
+    pub independent: bool,
+}
+
+pub struct ScriptOptions {
+    pub max_in_flight: usize,
+    pub all_independent: bool, // run every statement concurrently, ignoring markers
+    pub poll_interval: u64,
+    pub retry: RetryPolicy,
+}
+
+struct StatementReport {
+    index: usize,
+    query: String,
+    execution_ids: Vec<String>, // one per submission, the last one final
+    status: String,
+    duration: Duration,
+    data_scanned_bytes: Option<i64>,
+}
+
+// Split a script into statements on top-level semicolons, dropping comments.
+// A "-- @independent" comment before a statement marks it as independent.
+pub fn parse_script(script: &str) -> Vec<ScriptStatement> {
+    let mut statements: Vec<ScriptStatement> = Vec::new();
+    let mut query = String::new();
+    let mut independent = false;
+    let mut chars = script.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                // copy quoted text verbatim, doubled quotes simply toggle twice
+                query.push(c);
+                for q in chars.by_ref() {
+                    query.push(q);
+                    if q == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                if comment.trim_start_matches('-').trim() == INDEPENDENT_MARKER {
+                    independent = true;
+                }
+                query.push('\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for b in chars.by_ref() {
+                    if prev == '*' && b == '/' {
+                        break;
+                    }
+                    prev = b;
+                }
+                query.push(' ');
+            }
+            ';' => {
+                if !query.trim().is_empty() {
+                    statements.push(ScriptStatement {
+                        query: query.trim().to_string(),
+                        independent,
+                    });
+                }
+                query.clear();
+                independent = false;
+            }
+            _ => query.push(c),
+        }
+    }
+    if !query.trim().is_empty() {
+        statements.push(ScriptStatement {
+            query: query.trim().to_string(),
+            independent,
+        });
+    }
+    statements
+}
+
+// Run the statements in order, with consecutive independent statements running concurrently.
+// Returns false when any statement did not succeed.
+pub async fn run_script(
+    backend: Arc<dyn QueryBackend>,
+    ctx: &QueryContext,
+    statements: Vec<ScriptStatement>,
+    options: &ScriptOptions,
+) -> Result<bool> {
+    let semaphore = Arc::new(Semaphore::new(options.max_in_flight.max(1)));
+    let mut reports: Vec<StatementReport> = Vec::new();
+    let mut failed = false;
+
+    // Group consecutive independent statements into batches; every other statement is a batch of its own
+    let mut batches: Vec<Vec<(usize, String)>> = Vec::new();
+    let mut previous_independent = false;
+    for (index, statement) in statements.into_iter().enumerate() {
+        let independent = options.all_independent || statement.independent;
+        match batches.last_mut() {
+            Some(batch) if independent && previous_independent => {
+                batch.push((index + 1, statement.query))
+            }
+            _ => batches.push(vec![(index + 1, statement.query)]),
+        }
+        previous_independent = independent;
+    }
+
+    for batch in batches {
+        if failed {
+            // a failure stops the script, but the skipped statements are still reported
+            reports.extend(batch.into_iter().map(|(index, query)| StatementReport {
+                index,
+                query,
+                execution_ids: Vec::new(),
+                status: "SKIPPED".into(),
+                duration: Duration::ZERO,
+                data_scanned_bytes: None,
+            }));
+            continue;
+        }
+
+        let mut tasks = JoinSet::new();
+        for (index, query) in batch {
+            let backend = backend.clone();
+            let ctx = ctx.clone();
+            let semaphore = semaphore.clone();
+            let poll_interval = options.poll_interval;
+            let retry = options.retry;
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                run_one(backend.as_ref(), &ctx, index, query, poll_interval, retry).await
+            });
+        }
+        while let Some(report) = tasks.join_next().await {
+            let report = match report {
+                Ok(report) => report,
+                Err(e) => {
+                    println!("Statement task failed: {}", e);
+                    failed = true;
+                    continue;
+                }
+            };
+            println!(
+                "[{}] {} in {:.1}s",
+                report.index,
+                report.status,
+                report.duration.as_secs_f64()
+            );
+            failed |= report.status != "SUCCEEDED";
+            reports.push(report);
+        }
+    }
+
+    reports.sort_by_key(|r| r.index);
+    print_report(&reports);
+    Ok(!failed)
+}
+
+#[instrument(name = "statement", skip_all, fields(index, execution_id = field::Empty))]
+async fn run_one(
+    backend: &dyn QueryBackend,
+    ctx: &QueryContext,
+    index: usize,
+    query: String,
+    poll_interval: u64,
+    retry: RetryPolicy,
+) -> StatementReport {
+    let started = Instant::now();
+    let mut report = StatementReport {
+        index,
+        query,
+        execution_ids: Vec::new(),
+        status: "ERROR".into(),
+        duration: Duration::ZERO,
+        data_scanned_bytes: None,
+    };
+
+    let on_throttled = |delay: Duration| {
+        println!(
+            "[{}] Workgroup {} is saturated, retrying in {:.1}s",
+            index,
+            ctx.workgroup,
+            delay.as_secs_f64()
+        )
+    };
+    let mut retries = 0;
+    loop {
+        let execution_id = match backend
+            .submit_query(ctx, &report.query, &[], &on_throttled)
+            .await
+        {
+            Ok(execution_id) => execution_id,
+            Err(e) => {
+                println!("[{}] Error: {}", index, e);
+                break;
+            }
+        };
+        Span::current().record("execution_id", &execution_id);
+        report.execution_ids.push(execution_id.clone());
+        let summary = match backend.wait_for_query(&execution_id, poll_interval).await {
+            Ok(summary) => summary,
+            Err(e) => {
+                println!("[{}] Error: {}", index, e);
+                break;
+            }
+        };
+        if retry.should_retry(&report.query, &summary, retries) {
+            let delay = retry.backoff(retries);
+            retries += 1;
+            println!(
+                "[{}] {} failed transiently: {}; resubmitting in {}s (retry {} of {})",
+                index,
+                execution_id,
+                summary.failure_reason().unwrap_or_default(),
+                delay.as_secs(),
+                retries,
+                retry.max_retries
+            );
+            info!(retries, "resubmitting a transiently failed statement");
+            tokio::time::sleep(delay).await;
+            continue;
+        }
+        if let Some(reason) = summary.failure_reason() {
+            // one print, so the lines of concurrent statements do not interleave
+            match highlight_error(&report.query, &reason) {
+                Some(highlighted) => println!("[{}] {}\n{}", index, reason, highlighted),
+                None => println!("[{}] {}", index, reason),
+            }
+        }
+        report.status = summary.state;
+        report.data_scanned_bytes = summary.data_scanned_bytes;
+        break;
+    }
+    report.duration = started.elapsed();
+    info!(
+        status = %report.status,
+        duration_ms = report.duration.as_millis() as u64,
+        "statement finished"
+    );
+    report
+}
+
+fn print_report(reports: &[StatementReport]) {
+    let mut rows = vec![vec![
+        "#".to_string(),
+        "status".to_string(),
+        "duration".to_string(),
+        "data_scanned".to_string(),
+        "execution_ids".to_string(),
+        "query".to_string(),
+    ]];
+    for report in reports {
+        let mut query = report
+            .query
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        if query.chars().count() > 60 {
+            query = format!("{}...", query.chars().take(57).collect::<String>());
+        }
+        rows.push(vec![
+            report.index.to_string(),
+            report.status.clone(),
+            format!("{:.1}s", report.duration.as_secs_f64()),
+            report
+                .data_scanned_bytes
+                .map(format_bytes)
+                .unwrap_or_default(),
+            report.execution_ids.join(", "),
+            query,
+        ]);
+    }
+    print_table(&rows);
+}
@@ -0,0 +1,174 @@
+use crate::aws::cache;
+use crate::aws::error::{Result, ShellError};
+use crate::aws::glue::{Column, GlueService, TableInfo};
+use crate::format::print_table;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const CACHE_DIR: &str = "athena-shell/catalog";
+// A cached catalog older than this is crawled again before searching it
+const CACHE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Find tables by name, description, column name or column comment across every database.
+// Glue's SearchTables does the searching; when the session may not call it, the whole catalog
+// is crawled once with GetDatabases and GetTables and searched from a local cache instead.
+pub async fn search_catalog(
+    config: &aws_types::SdkConfig,
+    term: &str,
+    cache_key: &str,
+) -> Result<()> {
+    let glue = GlueService::new(config).await?;
+    let tables = match glue.search_tables(term).await {
+        Ok(tables) => tables,
+        Err(e @ ShellError::AccessDenied { .. }) => {
+            println!(
+                "Glue search is unavailable ({}), searching the cached catalog",
+                e
+            );
+            let mut tables = cached_catalog(&glue, cache_key).await?;
+            tables.retain(|table| {
+                contains(&table.name, term)
+                    || table
+                        .description
+                        .as_deref()
+                        .is_some_and(|d| contains(d, term))
+                    || !matching_columns(table, term).is_empty()
+            });
+            tables
+        }
+        Err(e) => return Err(e),
+    };
+    print_matches(&tables, term);
+    Ok(())
+}
+
+fn contains(text: &str, term: &str) -> bool {
+    text.to_lowercase().contains(&term.to_lowercase())
+}
+
+fn matching_columns<'a>(table: &'a TableInfo, term: &str) -> Vec<&'a Column> {
+    table
+        .columns
+        .iter()
+        .filter(|c| {
+            contains(&c.name, term) || c.comment.as_deref().is_some_and(|c| contains(c, term))
+        })
+        .collect()
+}
+
+// One row per matching column, or a single row without a column when the table matched
+// by its name or description
+fn print_matches(tables: &[TableInfo], term: &str) {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for table in tables {
+        let columns = matching_columns(table, term);
+        if columns.is_empty() {
+            rows.push(vec![
+                table.database.clone(),
+                table.name.clone(),
+                String::new(),
+                String::new(),
+            ]);
+        }
+        for column in columns {
+            rows.push(vec![
+                table.database.clone(),
+                table.name.clone(),
+                column.name.clone(),
+                column.data_type.clone(),
+            ]);
+        }
+    }
+    rows.sort();
+    rows.insert(
+        0,
+        vec![
+            "database".to_string(),
+            "table".to_string(),
+            "column".to_string(),
+            "type".to_string(),
+        ],
+    );
+    print_table(&rows);
+}
+
+// The catalog cached for this profile, region and assumed role, crawled again when missing or too old
+async fn cached_catalog(glue: &GlueService, cache_key: &str) -> Result<Vec<TableInfo>> {
+    if let Some(tables) = load_cache(cache_key) {
+        return Ok(tables);
+    }
+    let mut tables: Vec<TableInfo> = Vec::new();
+    for database in glue.list_databases().await? {
+        tables.extend(glue.get_tables(&database).await?);
+    }
+    save_cache(cache_key, &tables);
+    Ok(tables)
+}
+
+fn cache_path(cache_key: &str) -> Option<PathBuf> {
+    cache::cache_path(CACHE_DIR, cache_key)
+}
+
+fn load_cache(cache_key: &str) -> Option<Vec<TableInfo>> {
+    let contents = std::fs::read_to_string(cache_path(cache_key)?).ok()?;
+    let cached: Value = serde_json::from_str(&contents).ok()?;
+    let crawled = UNIX_EPOCH + Duration::from_secs(cached.get("crawled")?.as_u64()?);
+    if crawled + CACHE_MAX_AGE <= SystemTime::now() {
+        return None;
+    }
+    let text = |value: &Value, key: &str| value.get(key)?.as_str().map(String::from);
+    let tables = cached.get("tables")?.as_array()?;
+    Some(
+        tables
+            .iter()
+            .map(|table| TableInfo {
+                database: text(table, "database").unwrap_or_default(),
+                name: text(table, "name").unwrap_or_default(),
+                description: text(table, "description"),
+                table_type: None,
+                location: None,
+                owner: None,
+                update_time: None,
+                columns: table["columns"]
+                    .as_array()
+                    .map(Vec::as_slice)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|column| Column {
+                        name: text(column, "name").unwrap_or_default(),
+                        data_type: text(column, "type").unwrap_or_default(),
+                        comment: text(column, "comment"),
+                    })
+                    .collect(),
+            })
+            .collect(),
+    )
+}
+
+fn save_cache(cache_key: &str, tables: &[TableInfo]) {
+    let Some(path) = cache_path(cache_key) else {
+        return;
+    };
+    let crawled = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let tables: Vec<Value> = tables
+        .iter()
+        .map(|table| {
+            json!({
+                "database": table.database,
+                "name": table.name,
+                "description": table.description,
+                "columns": table.columns.iter().map(|column| json!({
+                    "name": column.name,
+                    "type": column.data_type,
+                    "comment": column.comment,
+                })).collect::<Vec<Value>>(),
+            })
+        })
+        .collect();
+    let contents = json!({ "crawled": crawled, "tables": tables });
+    cache::save(&path, &contents.to_string());
+}
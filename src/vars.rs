@@ -0,0 +1,82 @@
+use crate::aws::error::{Result, ShellError};
+use std::collections::BTreeMap;
+
+pub type Variables = BTreeMap<String, String>;
+
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Parse "name=value" from the command line
+pub fn parse_assignment(input: &str) -> std::result::Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((name, value)) if is_valid_name(name) => Ok((name.into(), value.into())),
+        _ => Err(format!("expected name=value, got: {}", input)),
+    }
+}
+
+// Strip one level of single quotes from a "\set" value, undoing doubled-quote escapes
+pub fn unquote(value: &str) -> String {
+    match value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        Some(inner) => inner.replace("''", "'"),
+        None => value.into(),
+    }
+}
+
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+pub fn quote_identifier(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+// Substitute variable references in the input:
+//   ${name}    the raw value
+//   ${'name'}  the value as a quoted SQL string literal
+//   ${"name"}  the value as a quoted SQL identifier
+pub fn interpolate(input: &str, vars: &Variables) -> Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            // no closing brace, so this is not a reference
+            output.push_str(&rest[start..]);
+            return Ok(output);
+        };
+
+        let reference = &after[..end];
+        let (name, quote): (&str, fn(&str) -> String) = if let Some(name) = reference
+            .strip_prefix('\'')
+            .and_then(|r| r.strip_suffix('\''))
+        {
+            (name, quote_literal)
+        } else if let Some(name) = reference
+            .strip_prefix('"')
+            .and_then(|r| r.strip_suffix('"'))
+        {
+            (name, quote_identifier)
+        } else {
+            (reference, str::to_owned)
+        };
+
+        if !is_valid_name(name) {
+            // leave anything that does not look like a reference untouched
+            output.push_str("${");
+            rest = after;
+            continue;
+        }
+        let value = vars
+            .get(name)
+            .ok_or_else(|| ShellError::UndefinedVariable(name.into()))?;
+        output.push_str(&quote(value));
+        rest = &after[end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
@@ -0,0 +1,476 @@
+mod common;
+
+use athena_shell::aws::athena::{AthenaService, QueryContext};
+use athena_shell::aws::error::ShellError;
+use athena_shell::aws::glue::GlueService;
+use athena_shell::aws::s3::S3Service;
+use athena_shell::backend::{AthenaBackend, QueryBackend};
+use aws_credential_types::provider::error::CredentialsError;
+use aws_credential_types::provider::{ProvideCredentials, SharedCredentialsProvider, future};
+use aws_smithy_types::timeout::TimeoutConfig;
+use common::{FakeAws, Reply, query_execution, result_page, started};
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+const TOKEN: &str = "00000000000000000000000000000001";
+
+fn context() -> QueryContext {
+    QueryContext {
+        database: "sales".into(),
+        workgroup: "primary".into(),
+        output_bucket: None,
+        result_reuse_max_age: None,
+    }
+}
+
+#[tokio::test]
+async fn submits_with_context_and_parameters() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    let athena = AthenaService::new(&fake.sdk_config().await).await.unwrap();
+
+    let params = vec!["'2024-01-01'".to_string()];
+    let execution_id = athena
+        .invoke_query(
+            &context(),
+            "SELECT * FROM orders WHERE day = ?",
+            &params,
+            TOKEN,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(execution_id, "q-1");
+    let request = fake.requests("StartQueryExecution")[0].json();
+    assert_eq!(request["QueryString"], "SELECT * FROM orders WHERE day = ?");
+    assert_eq!(request["QueryExecutionContext"]["Database"], "sales");
+    assert_eq!(request["WorkGroup"], "primary");
+    assert_eq!(request["ExecutionParameters"], json!(["'2024-01-01'"]));
+}
+
+#[tokio::test]
+async fn follows_result_pages() {
+    let fake = FakeAws::start();
+    fake.on(
+        "GetQueryResults",
+        [
+            result_page(&[&["id", "name"], &["1", "a"]], Some("page-2")),
+            result_page(&[&["2", "b"]], None),
+        ],
+    );
+    let athena = AthenaService::new(&fake.sdk_config().await).await.unwrap();
+
+    let rows = athena.get_query_results("q-1").await.unwrap();
+
+    assert_eq!(
+        rows,
+        vec![vec!["id", "name"], vec!["1", "a"], vec!["2", "b"]]
+    );
+    let requests = fake.requests("GetQueryResults");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].json()["NextToken"], "page-2");
+}
+
+#[tokio::test]
+async fn reports_state_transitions() {
+    let fake = FakeAws::start();
+    fake.on(
+        "GetQueryExecution",
+        [
+            query_execution("q-1", "QUEUED", None),
+            query_execution("q-1", "RUNNING", None),
+            query_execution("q-1", "FAILED", Some("SYNTAX_ERROR: line 1:8")),
+        ],
+    );
+    let athena = AthenaService::new(&fake.sdk_config().await).await.unwrap();
+
+    let mut states = Vec::new();
+    for _ in 0..3 {
+        states.push(athena.get_query_summary("q-1").await.unwrap());
+    }
+
+    let names: Vec<&str> = states.iter().map(|s| s.state.as_str()).collect();
+    assert_eq!(names, ["QUEUED", "RUNNING", "FAILED"]);
+    assert!(states[2].is_finished());
+    assert_eq!(
+        states[2].state_change_reason.as_deref(),
+        Some("SYNTAX_ERROR: line 1:8")
+    );
+    assert_eq!(states[2].data_scanned_bytes, Some(1024));
+}
+
+#[tokio::test]
+async fn retries_throttled_requests() {
+    let fake = FakeAws::start();
+    fake.on(
+        "StartQueryExecution",
+        [
+            Reply::json_error(400, "ThrottlingException", "Rate exceeded"),
+            started("q-1"),
+        ],
+    );
+    let athena = AthenaService::new(&fake.sdk_config().await).await.unwrap();
+
+    let execution_id = athena
+        .invoke_query(&context(), "SELECT 1", &[], TOKEN)
+        .await;
+
+    assert_eq!(execution_id.unwrap(), "q-1");
+    let requests = fake.requests("StartQueryExecution");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].json()["ClientRequestToken"], TOKEN);
+    assert_eq!(requests[1].json()["ClientRequestToken"], TOKEN);
+}
+
+#[tokio::test]
+async fn resubmits_throttled_statements_with_the_same_token() {
+    let fake = FakeAws::start();
+    let throttled = || Reply::json_error(400, "TooManyRequestsException", "Too many queries");
+    fake.on(
+        "StartQueryExecution",
+        [throttled(), throttled(), throttled(), started("q-1")],
+    );
+    let backend = AthenaBackend::new(&fake.sdk_config().await).await.unwrap();
+    let reports = AtomicUsize::new(0);
+
+    let execution_id = backend
+        .submit_query(&context(), "SELECT 1", &[], &|_| {
+            reports.fetch_add(1, Ordering::Relaxed);
+        })
+        .await;
+
+    assert_eq!(execution_id.unwrap(), "q-1");
+    assert_eq!(reports.load(Ordering::Relaxed), 1);
+    let tokens: Vec<_> = fake
+        .requests("StartQueryExecution")
+        .iter()
+        .map(|r| r.json()["ClientRequestToken"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(tokens.len(), 4);
+    assert!(tokens[0].len() >= 32);
+    assert!(tokens.iter().all(|token| *token == tokens[0]));
+}
+
+#[tokio::test]
+async fn surfaces_failures() {
+    let fake = FakeAws::start();
+    fake.on(
+        "StartQueryExecution",
+        [Reply::json_error(
+            400,
+            "InvalidRequestException",
+            "Database does not exist",
+        )],
+    );
+    fake.on(
+        "GetQueryExecution",
+        [Reply::json_error(
+            400,
+            "ExpiredTokenException",
+            "The security token has expired",
+        )],
+    );
+    let athena = AthenaService::new(&fake.sdk_config().await).await.unwrap();
+
+    let invalid = athena
+        .invoke_query(&context(), "SELECT 1", &[], TOKEN)
+        .await;
+    let expired = athena.get_query_summary("q-1").await;
+
+    assert!(invalid.is_err_and(|e| !e.is_expired_credentials()));
+    assert!(expired.is_err_and(|e| e.is_expired_credentials()));
+}
+
+#[tokio::test]
+async fn lists_catalogs_and_databases_across_pages() {
+    let fake = FakeAws::start();
+    fake.on(
+        "ListDataCatalogs",
+        [
+            Reply::json(json!({
+                "DataCatalogsSummary": [{ "CatalogName": "AwsDataCatalog" }],
+                "NextToken": "more"
+            })),
+            Reply::json(json!({ "DataCatalogsSummary": [{ "CatalogName": "lake" }] })),
+        ],
+    );
+    fake.on(
+        "ListDatabases",
+        [Reply::json(
+            json!({ "DatabaseList": [{ "Name": "sales" }, { "Name": "logs" }] }),
+        )],
+    );
+    let athena = AthenaService::new(&fake.sdk_config().await).await.unwrap();
+
+    assert_eq!(
+        athena.list_catalogs().await.unwrap(),
+        ["AwsDataCatalog", "lake"]
+    );
+    assert_eq!(
+        athena.list_databases("AwsDataCatalog").await.unwrap(),
+        ["sales", "logs"]
+    );
+    assert_eq!(
+        fake.requests("ListDataCatalogs")[1].json()["NextToken"],
+        "more"
+    );
+}
+
+#[tokio::test]
+async fn lists_glue_tables_across_pages() {
+    let fake = FakeAws::start();
+    fake.on(
+        "GetTables",
+        [
+            Reply::json(json!({ "TableList": [{ "Name": "orders" }], "NextToken": "t-2" })),
+            Reply::json(json!({ "TableList": [{ "Name": "customers" }] })),
+        ],
+    );
+    let glue = GlueService::new(&fake.sdk_config().await).await.unwrap();
+
+    let tables = glue.list_tables("sales").await.unwrap();
+
+    assert_eq!(tables, ["orders", "customers"]);
+    let requests = fake.requests("GetTables");
+    assert_eq!(requests[0].json()["DatabaseName"], "sales");
+    assert_eq!(requests[1].json()["NextToken"], "t-2");
+}
+
+#[tokio::test]
+async fn lists_glue_partitions_matching_an_expression() {
+    let fake = FakeAws::start();
+    fake.on(
+        "GetPartitions",
+        [
+            Reply::json(json!({
+                "Partitions": [{
+                    "Values": ["2024", "07"],
+                    "StorageDescriptor": { "Location": "s3://lake/orders/year=2024/month=07" },
+                    "CreationTime": 1720000000
+                }],
+                "NextToken": "p-2"
+            })),
+            Reply::json(json!({ "Partitions": [{ "Values": ["2024", "08"] }] })),
+        ],
+    );
+    let glue = GlueService::new(&fake.sdk_config().await).await.unwrap();
+
+    let partitions = glue
+        .list_partitions("sales", "orders", Some("year = '2024'"))
+        .await
+        .unwrap();
+
+    let values: Vec<_> = partitions.iter().map(|p| p.values.clone()).collect();
+    assert_eq!(values, [["2024", "07"], ["2024", "08"]]);
+    assert_eq!(
+        partitions[0].location.as_deref(),
+        Some("s3://lake/orders/year=2024/month=07")
+    );
+    assert!(partitions[0].creation_time.is_some());
+    assert!(partitions[1].location.is_none());
+    let requests = fake.requests("GetPartitions");
+    assert_eq!(requests[0].json()["Expression"], "year = '2024'");
+    assert_eq!(requests[1].json()["NextToken"], "p-2");
+}
+
+#[tokio::test]
+async fn counts_s3_objects_across_pages() {
+    let fake = FakeAws::start();
+    fake.on(
+        "ListObjectsV2",
+        [
+            Reply::xml(
+                "<ListBucketResult><Name>results</Name><IsTruncated>true</IsTruncated>\
+                 <NextContinuationToken>c-2</NextContinuationToken>\
+                 <Contents><Key>out/</Key></Contents>\
+                 <Contents><Key>out/q-1.csv</Key></Contents></ListBucketResult>",
+            ),
+            Reply::xml(
+                "<ListBucketResult><Name>results</Name><IsTruncated>false</IsTruncated>\
+                 <Contents><Key>out/q-2.csv</Key></Contents></ListBucketResult>",
+            ),
+        ],
+    );
+    let s3 = S3Service::new(&fake.sdk_config().await, true)
+        .await
+        .unwrap();
+
+    let count = s3.count_objects("results", "out/").await.unwrap();
+
+    // the folder marker is not counted
+    assert_eq!(count, 2);
+    let requests = fake.requests("ListObjectsV2");
+    assert!(requests[0].target.starts_with("/results/?"));
+    assert!(requests[1].target.contains("continuation-token=c-2"));
+}
+
+#[tokio::test]
+async fn surfaces_s3_errors() {
+    let fake = FakeAws::start();
+    fake.on(
+        "ListObjectsV2",
+        [Reply::xml_error(403, "AccessDenied", "Access Denied")],
+    );
+    let s3 = S3Service::new(&fake.sdk_config().await, true)
+        .await
+        .unwrap();
+
+    assert!(s3.count_objects("results", "out/").await.is_err());
+}
+
+#[tokio::test]
+async fn classifies_failed_requests() {
+    let fake = FakeAws::start();
+    fake.on(
+        "ListDataCatalogs",
+        [Reply::json_error(
+            400,
+            "AccessDeniedException",
+            "not authorized",
+        )],
+    );
+    fake.on(
+        "ListDatabases",
+        [Reply::json_error(
+            400,
+            "MetadataException",
+            "Catalog nope not found",
+        )],
+    );
+    fake.on(
+        "StartQueryExecution",
+        [Reply::json(json!({
+            "__type": "InvalidRequestException",
+            "Message": "line 1:8: mismatched input",
+            "AthenaErrorCode": "INVALID_INPUT"
+        }))
+        .with_status(400)],
+    );
+    let athena = AthenaService::new(&fake.sdk_config().await).await.unwrap();
+
+    let denied = athena.list_catalogs().await.err().unwrap();
+    let missing = athena.list_databases("nope").await.err().unwrap();
+    let invalid = athena
+        .invoke_query(&context(), "SELEC 1", &[], TOKEN)
+        .await
+        .err()
+        .unwrap();
+
+    assert!(
+        matches!(denied, ShellError::AccessDenied { .. }),
+        "{}",
+        denied
+    );
+    assert!(
+        matches!(missing, ShellError::NotFound { .. }),
+        "{}",
+        missing
+    );
+    assert!(
+        invalid.to_string().contains("(INVALID_INPUT): line 1:8"),
+        "{}",
+        invalid
+    );
+}
+
+#[tokio::test]
+async fn classifies_unreachable_endpoints() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    let config = FakeAws::start()
+        .sdk_config()
+        .await
+        .to_builder()
+        .endpoint_url(url)
+        .build();
+    let athena = AthenaService::new(&config).await.unwrap();
+
+    let unreachable = athena.list_catalogs().await.err().unwrap();
+
+    assert!(
+        matches!(unreachable, ShellError::Network { .. }),
+        "{}",
+        unreachable
+    );
+}
+
+#[tokio::test]
+async fn classifies_timed_out_requests() {
+    // Accepts connections but never answers them
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let config = FakeAws::start()
+        .sdk_config()
+        .await
+        .to_builder()
+        .endpoint_url(url)
+        .timeout_config(
+            TimeoutConfig::builder()
+                .operation_timeout(Duration::from_secs(1))
+                .build(),
+        )
+        .build();
+    let athena = AthenaService::new(&config).await.unwrap();
+
+    let timed_out = athena.list_catalogs().await.err().unwrap();
+
+    assert!(
+        matches!(timed_out, ShellError::Network { .. }),
+        "{}",
+        timed_out
+    );
+    drop(listener);
+}
+
+#[derive(Debug)]
+struct FailingCredentials(&'static str);
+
+impl ProvideCredentials for FailingCredentials {
+    fn provide_credentials<'a>(&'a self) -> future::ProvideCredentials<'a>
+    where
+        Self: 'a,
+    {
+        future::ProvideCredentials::ready(Err(CredentialsError::provider_error(self.0)))
+    }
+}
+
+async fn failing_athena(fake: &FakeAws, message: &'static str) -> AthenaService {
+    let config = fake
+        .sdk_config()
+        .await
+        .to_builder()
+        .credentials_provider(SharedCredentialsProvider::new(FailingCredentials(message)))
+        .build();
+    AthenaService::new(&config).await.unwrap()
+}
+
+#[tokio::test]
+async fn classifies_credentials_provider_failures() {
+    let fake = FakeAws::start();
+
+    let expired = failing_athena(&fake, "the SSO session has expired")
+        .await
+        .list_catalogs()
+        .await
+        .err()
+        .unwrap();
+    let missing = failing_athena(&fake, "no profile named dev")
+        .await
+        .list_catalogs()
+        .await
+        .err()
+        .unwrap();
+
+    assert!(
+        matches!(expired, ShellError::ExpiredCredentials { .. }),
+        "{}",
+        expired
+    );
+    assert!(
+        matches!(missing, ShellError::CredentialsUnavailable { .. }),
+        "{}",
+        missing
+    );
+}
@@ -0,0 +1,326 @@
+// An in-process stand-in for Athena, Glue and S3 that serves scripted responses, so the
+// SDK clients and the shell can be exercised end to end without an AWS account
+#![allow(dead_code)]
+
+use aws_config::BehaviorVersion;
+use aws_credential_types::Credentials;
+use serde_json::{Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Reply {
+    // A response of the Athena and Glue JSON protocol
+    pub fn json(body: Value) -> Self {
+        Reply {
+            status: 200,
+            content_type: "application/x-amz-json-1.1",
+            body: body.to_string(),
+        }
+    }
+
+    // An error of the JSON protocol, e.g. ThrottlingException or InvalidRequestException
+    pub fn json_error(status: u16, code: &str, message: &str) -> Self {
+        Reply {
+            status,
+            content_type: "application/x-amz-json-1.1",
+            body: json!({ "__type": code, "Message": message }).to_string(),
+        }
+    }
+
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    // A response of the S3 REST-XML protocol
+    pub fn xml(body: &str) -> Self {
+        Reply {
+            status: 200,
+            content_type: "application/xml",
+            body: body.to_string(),
+        }
+    }
+
+    pub fn xml_error(status: u16, code: &str, message: &str) -> Self {
+        Reply {
+            status,
+            content_type: "application/xml",
+            body: format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+                 <Error><Code>{code}</Code><Message>{message}</Message></Error>"
+            ),
+        }
+    }
+}
+
+// A request received by the fake, named after the operation it invokes
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub operation: String,
+    pub target: String, // path and query string
+    pub body: String,
+}
+
+impl Request {
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    replies: HashMap<String, VecDeque<Reply>>,
+    requests: Vec<Request>,
+}
+
+pub struct FakeAws {
+    port: u16,
+    state: Arc<Mutex<State>>,
+    home: PathBuf, // home directory of the shells run against the fake, so caches start empty
+}
+
+impl FakeAws {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind the fake server");
+        let port = listener.local_addr().unwrap().port();
+        let state = Arc::new(Mutex::new(State::default()));
+        let server_state = state.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = server_state.clone();
+                std::thread::spawn(move || serve(stream, state));
+            }
+        });
+        let home =
+            std::env::temp_dir().join(format!("athena-shell-home-{}-{}", std::process::id(), port));
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(&home).expect("create the home directory");
+        FakeAws { port, state, home }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    // Queue replies for an operation, served in order; the last one keeps being served
+    pub fn on(&self, operation: &str, replies: impl IntoIterator<Item = Reply>) -> &Self {
+        let mut state = self.state.lock().unwrap();
+        state
+            .replies
+            .entry(operation.to_string())
+            .or_default()
+            .extend(replies);
+        self
+    }
+
+    pub fn requests(&self, operation: &str) -> Vec<Request> {
+        let state = self.state.lock().unwrap();
+        state
+            .requests
+            .iter()
+            .filter(|r| r.operation == operation)
+            .cloned()
+            .collect()
+    }
+
+    // A config whose clients all talk to the fake, with static credentials
+    pub async fn sdk_config(&self) -> aws_types::SdkConfig {
+        aws_config::defaults(BehaviorVersion::latest())
+            .region("us-east-1")
+            .credentials_provider(Credentials::new("AKIDTEST", "secret", None, None, "test"))
+            .endpoint_url(self.url())
+            .load()
+            .await
+    }
+
+    // The shared credentials file of the shells run against the fake, absent until written
+    pub fn credentials_file(&self) -> PathBuf {
+        self.home.join("shared-credentials")
+    }
+
+    // Run the shell against the fake, feeding it stdin and waiting for it to exit
+    pub fn run_shell(&self, args: &[&str], stdin: &str) -> Output {
+        let mut child = self.spawn_shell(args);
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.as_bytes())
+            .unwrap();
+        child.wait_with_output().expect("wait for athena-shell")
+    }
+
+    // Start the shell against the fake, leaving its stdin open
+    pub fn spawn_shell(&self, args: &[&str]) -> Child {
+        Command::new(env!("CARGO_BIN_EXE_athena-shell"))
+            .args(["--timeout", "1", "--athena-endpoint-url", &self.url()])
+            .args(["--glue-endpoint-url", &self.url()])
+            .args(["--s3-endpoint-url", &self.url(), "--s3-force-path-style"])
+            .args(args)
+            .env_clear()
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+            .env("HOME", &self.home)
+            .env("AWS_ACCESS_KEY_ID", "AKIDTEST")
+            .env("AWS_SECRET_ACCESS_KEY", "secret")
+            .env("AWS_REGION", "us-east-1")
+            .env("AWS_CONFIG_FILE", "/nonexistent/config")
+            .env("AWS_SHARED_CREDENTIALS_FILE", self.credentials_file())
+            .env("AWS_EC2_METADATA_DISABLED", "true")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("start athena-shell")
+    }
+}
+
+impl Drop for FakeAws {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.home);
+    }
+}
+
+// Serve the requests of one keep-alive connection until the client closes it
+fn serve(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let target = parts.next().unwrap_or_default().to_string();
+
+        let mut headers: HashMap<String, String> = HashMap::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+        let length = headers
+            .get("content-length")
+            .and_then(|l| l.parse::<usize>().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+
+        let operation = operation_name(&method, &target, &headers);
+        let reply = {
+            let mut state = state.lock().unwrap();
+            state.requests.push(Request {
+                operation: operation.clone(),
+                target: target.clone(),
+                body: String::from_utf8_lossy(&body).into_owned(),
+            });
+            match state.replies.get_mut(&operation) {
+                Some(replies) if replies.len() > 1 => replies.pop_front().unwrap(),
+                Some(replies) if !replies.is_empty() => replies[0].clone(),
+                _ => Reply::json_error(
+                    400,
+                    "InvalidRequestException",
+                    &format!("no scripted response for {}", operation),
+                ),
+            }
+        };
+        let response = format!(
+            "HTTP/1.1 {} Fake\r\nContent-Type: {}\r\nContent-Length: {}\r\nx-amzn-RequestId: fake\r\n\r\n{}",
+            reply.status,
+            reply.content_type,
+            reply.body.len(),
+            reply.body
+        );
+        if writer.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+// JSON protocols name the operation in X-Amz-Target, S3 in the method and query string
+fn operation_name(method: &str, target: &str, headers: &HashMap<String, String>) -> String {
+    if let Some(amz_target) = headers.get("x-amz-target") {
+        return amz_target
+            .rsplit('.')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+    }
+    match (method, target.contains("list-type=2")) {
+        ("GET", true) => "ListObjectsV2".to_string(),
+        _ => format!("{} {}", method, target),
+    }
+}
+
+// Athena responses
+
+pub fn query_execution(execution_id: &str, state: &str, reason: Option<&str>) -> Reply {
+    let mut status = json!({ "State": state });
+    if let Some(reason) = reason {
+        status["StateChangeReason"] = json!(reason);
+        status["AthenaError"] =
+            json!({ "ErrorCategory": 2, "ErrorType": 1006, "Retryable": false });
+    }
+    Reply::json(json!({
+        "QueryExecution": {
+            "QueryExecutionId": execution_id,
+            "Status": status,
+            "Statistics": { "DataScannedInBytes": 1024, "TotalExecutionTimeInMillis": 1200 }
+        }
+    }))
+}
+
+// A failure Athena flags as retryable, like S3 slowing down
+pub fn failed_transiently(execution_id: &str) -> Reply {
+    Reply::json(json!({
+        "QueryExecution": {
+            "QueryExecutionId": execution_id,
+            "Status": {
+                "State": "FAILED",
+                "StateChangeReason": "HIVE_CANNOT_OPEN_SPLIT: S3 SlowDown",
+                "AthenaError": { "ErrorCategory": 1, "ErrorType": 1000, "Retryable": true }
+            }
+        }
+    }))
+}
+
+// A page of results; the first page of a statement starts with the column names
+pub fn result_page(rows: &[&[&str]], next_token: Option<&str>) -> Reply {
+    let rows: Vec<Value> = rows
+        .iter()
+        .map(|row| {
+            let data: Vec<Value> = row.iter().map(|v| json!({ "VarCharValue": v })).collect();
+            json!({ "Data": data })
+        })
+        .collect();
+    let mut page = json!({ "ResultSet": { "Rows": rows } });
+    if let Some(token) = next_token {
+        page["NextToken"] = json!(token);
+    }
+    Reply::json(page)
+}
+
+pub fn started(execution_id: &str) -> Reply {
+    Reply::json(json!({ "QueryExecutionId": execution_id }))
+}
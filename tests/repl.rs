@@ -0,0 +1,743 @@
+mod common;
+
+use common::{FakeAws, Reply, failed_transiently, query_execution, result_page, started};
+use serde_json::json;
+use std::io::Write;
+use std::time::Duration;
+
+fn transcript(output: &std::process::Output) -> String {
+    format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    )
+}
+
+#[test]
+fn runs_a_query_end_to_end() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    fake.on(
+        "GetQueryExecution",
+        [
+            query_execution("q-1", "QUEUED", None),
+            query_execution("q-1", "RUNNING", None),
+            query_execution("q-1", "SUCCEEDED", None),
+        ],
+    );
+    fake.on(
+        "GetQueryResults",
+        [
+            result_page(&[&["id", "name"], &["1", "alpha"]], Some("page-2")),
+            result_page(&[&["2", "beta"]], None),
+        ],
+    );
+
+    let output = fake.run_shell(
+        &["--database", "sales"],
+        "SELECT id, name\nFROM orders;\n\\q\n",
+    );
+
+    let transcript = transcript(&output);
+    assert!(output.status.success(), "{}", transcript);
+    assert!(transcript.contains("alpha"), "{}", transcript);
+    assert!(transcript.contains("beta"), "{}", transcript);
+    assert!(transcript.contains("(2 rows)"), "{}", transcript);
+    assert!(
+        transcript.contains("Using credentials from: environment variables"),
+        "{}",
+        transcript
+    );
+    let request = fake.requests("StartQueryExecution")[0].json();
+    assert_eq!(request["QueryString"], "SELECT id, name\nFROM orders");
+    assert_eq!(request["QueryExecutionContext"]["Database"], "sales");
+}
+
+#[test]
+fn waits_for_a_long_running_query() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    let running = (0..6).map(|_| query_execution("q-1", "RUNNING", None));
+    fake.on("GetQueryExecution", running);
+    fake.on(
+        "GetQueryExecution",
+        [query_execution("q-1", "SUCCEEDED", None)],
+    );
+    fake.on("GetQueryResults", [result_page(&[&["id"], &["1"]], None)]);
+
+    let output = fake.run_shell(&[], "SELECT 1;\n\\q\n");
+
+    let transcript = transcript(&output);
+    assert!(transcript.contains("(1 row)"), "{}", transcript);
+    assert_eq!(fake.requests("StartQueryExecution").len(), 1);
+}
+
+#[test]
+fn polls_a_submitted_query_again_after_renewing_credentials() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    fake.on(
+        "GetQueryExecution",
+        [
+            Reply::json_error(
+                400,
+                "ExpiredTokenException",
+                "The security token has expired",
+            ),
+            query_execution("q-1", "SUCCEEDED", None),
+        ],
+    );
+    fake.on("GetQueryResults", [result_page(&[&["id"], &["1"]], None)]);
+
+    let output = fake.run_shell(&[], "INSERT INTO orders VALUES (1);\nr\n\\q\n");
+
+    let transcript = transcript(&output);
+    assert!(transcript.contains("Credentials renewed"), "{}", transcript);
+    assert!(transcript.contains("(1 row)"), "{}", transcript);
+    assert_eq!(fake.requests("StartQueryExecution").len(), 1);
+}
+
+#[test]
+fn substitutes_explained_variables_once() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    fake.on(
+        "GetQueryExecution",
+        [query_execution("q-1", "SUCCEEDED", None)],
+    );
+    fake.on("GetQueryResults", [result_page(&[&["Query Plan"]], None)]);
+
+    let output = fake.run_shell(
+        &["--var", "x=${y}", "--var", "y=1) UNION ALL SELECT (2"],
+        "\\explain SELECT ${'x'}\n\\q\n",
+    );
+
+    let transcript = transcript(&output);
+    let query = fake.requests("StartQueryExecution")[0].json()["QueryString"].clone();
+    assert_eq!(
+        query, "EXPLAIN (FORMAT JSON) SELECT '${y}'",
+        "{}",
+        transcript
+    );
+}
+
+#[test]
+fn stops_a_running_query_on_ctrl_c() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    fake.on(
+        "GetQueryExecution",
+        [query_execution("q-1", "RUNNING", None)],
+    );
+    fake.on("StopQueryExecution", [Reply::json(json!({}))]);
+
+    let mut shell = fake.spawn_shell(&[]);
+    let mut stdin = shell.stdin.take().unwrap();
+    stdin.write_all(b"SELECT 1;\n").unwrap();
+    while fake.requests("GetQueryExecution").is_empty() {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let interrupted = std::process::Command::new("kill")
+        .args(["-INT", &shell.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(interrupted.success());
+    while fake.requests("StopQueryExecution").is_empty() {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    stdin.write_all(b"\\q\n").unwrap();
+    drop(stdin);
+    let output = shell.wait_with_output().unwrap();
+
+    let transcript = transcript(&output);
+    assert!(output.status.success(), "{}", transcript);
+    assert!(
+        transcript.contains("Query q-1 was cancelled"),
+        "{}",
+        transcript
+    );
+    assert_eq!(
+        fake.requests("StopQueryExecution")[0].json()["QueryExecutionId"],
+        "q-1"
+    );
+}
+
+#[test]
+fn keeps_going_after_a_failed_query() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    fake.on(
+        "GetQueryExecution",
+        [query_execution(
+            "q-1",
+            "FAILED",
+            Some("TABLE_NOT_FOUND: Table 'sales.nope' does not exist"),
+        )],
+    );
+    fake.on(
+        "ListDataCatalogs",
+        [Reply::json(
+            json!({ "DataCatalogsSummary": [{ "CatalogName": "AwsDataCatalog" }] }),
+        )],
+    );
+
+    let output = fake.run_shell(&[], "SELECT * FROM nope;\n\\lc\n\\q\n");
+
+    let transcript = transcript(&output);
+    assert!(
+        transcript.contains("does not exist [user error 1006, not retryable]"),
+        "{}",
+        transcript
+    );
+    assert!(transcript.contains("AwsDataCatalog"), "{}", transcript);
+    assert!(fake.requests("GetQueryResults").is_empty());
+}
+
+#[test]
+fn lists_prepared_statements_as_a_table() {
+    let fake = FakeAws::start();
+    fake.on(
+        "ListPreparedStatements",
+        [Reply::json(json!({
+            "PreparedStatements": [
+                { "StatementName": "top_orders" },
+                { "StatementName": "by_region" }
+            ]
+        }))],
+    );
+
+    let output = fake.run_shell(&[], "\\prepare\n\\q\n");
+
+    let transcript = transcript(&output);
+    let header = transcript.find("Statement").expect(&transcript);
+    let by_region = transcript.find("by_region").expect(&transcript);
+    let top_orders = transcript.find("top_orders").expect(&transcript);
+    assert!(
+        header < by_region && by_region < top_orders,
+        "{}",
+        transcript
+    );
+    assert!(transcript.contains("(2 rows)"), "{}", transcript);
+}
+
+#[test]
+fn lists_the_catalog() {
+    let fake = FakeAws::start();
+    fake.on(
+        "ListDataCatalogs",
+        [Reply::json(
+            json!({ "DataCatalogsSummary": [{ "CatalogName": "AwsDataCatalog" }] }),
+        )],
+    );
+    fake.on(
+        "ListDatabases",
+        [Reply::json(
+            json!({ "DatabaseList": [{ "Name": "sales" }] }),
+        )],
+    );
+    fake.on(
+        "GetTables",
+        [
+            Reply::json(json!({ "TableList": [{ "Name": "orders" }], "NextToken": "t" })),
+            Reply::json(json!({ "TableList": [{ "Name": "customers" }] })),
+        ],
+    );
+
+    let output = fake.run_shell(&[], "\\lc\n\\ld AwsDataCatalog\n\\lt sales\n\\q\n");
+
+    let transcript = transcript(&output);
+    assert!(transcript.contains(" AwsDataCatalog \n"), "{}", transcript);
+    assert!(transcript.contains(" sales    \n"), "{}", transcript);
+    assert!(
+        transcript.contains(" customers \n orders    \n(2 rows)"),
+        "{}",
+        transcript
+    );
+    assert_eq!(
+        fake.requests("GetTables")[0].json()["DatabaseName"],
+        "sales"
+    );
+}
+
+#[test]
+fn reads_profiles_from_the_shared_credentials_file() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    fake.on(
+        "GetQueryExecution",
+        [query_execution("q-1", "SUCCEEDED", None)],
+    );
+    fake.on(
+        "GetQueryResults",
+        [result_page(&[&["_col0"], &["1"]], None)],
+    );
+    std::fs::write(
+        fake.credentials_file(),
+        "[dev]\naws_access_key_id = AKIDDEV\naws_secret_access_key = secret\n",
+    )
+    .unwrap();
+
+    let output = fake.run_shell(&["--profile", "dev", "-e", "SELECT 1"], "");
+
+    let transcript = transcript(&output);
+    assert!(output.status.success(), "{}", transcript);
+    assert!(transcript.contains("Loaded profile: dev"), "{}", transcript);
+    assert!(transcript.contains("(1 row)"), "{}", transcript);
+}
+
+#[test]
+fn retries_a_throttled_submission() {
+    let fake = FakeAws::start();
+    fake.on(
+        "StartQueryExecution",
+        [
+            Reply::json_error(400, "ThrottlingException", "Rate exceeded"),
+            started("q-1"),
+        ],
+    );
+    fake.on(
+        "GetQueryExecution",
+        [query_execution("q-1", "SUCCEEDED", None)],
+    );
+    fake.on(
+        "GetQueryResults",
+        [result_page(&[&["_col0"], &["1"]], None)],
+    );
+
+    let output = fake.run_shell(&["-e", "SELECT 1"], "");
+
+    let transcript = transcript(&output);
+    assert!(output.status.success(), "{}", transcript);
+    assert!(transcript.contains("(1 row)"), "{}", transcript);
+    assert_eq!(fake.requests("StartQueryExecution").len(), 2);
+}
+
+#[test]
+fn exits_with_failure_when_a_statement_fails() {
+    let fake = FakeAws::start();
+    fake.on(
+        "StartQueryExecution",
+        [Reply::json_error(
+            400,
+            "InvalidRequestException",
+            "line 1:8: mismatched input",
+        )],
+    );
+
+    let output = fake.run_shell(&["-e", "SELEC 1"], "");
+
+    let transcript = transcript(&output);
+    assert_eq!(output.status.code(), Some(1), "{}", transcript);
+    assert!(transcript.contains("mismatched input"), "{}", transcript);
+}
+
+#[test]
+fn replays_a_recorded_session_offline() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    fake.on(
+        "GetQueryExecution",
+        [
+            query_execution("q-1", "RUNNING", None),
+            query_execution("q-1", "SUCCEEDED", None),
+        ],
+    );
+    fake.on(
+        "GetQueryResults",
+        [result_page(&[&["name"], &["recorded-row"]], None)],
+    );
+    let cassette = std::env::temp_dir().join(format!("athena-shell-{}.json", std::process::id()));
+    let cassette = cassette.to_str().unwrap();
+
+    let recorded = fake.run_shell(&["--record", cassette], "SELECT name FROM orders;\n\\q\n");
+    let requests = fake.requests("GetQueryExecution").len();
+    let replayed = fake.run_shell(&["--replay", cassette], "SELECT name FROM orders;\n\\q\n");
+
+    let contents = std::fs::read_to_string(cassette).unwrap();
+    let _ = std::fs::remove_file(cassette);
+    assert!(
+        transcript(&recorded).contains("recorded-row"),
+        "{}",
+        transcript(&recorded)
+    );
+    let replayed = transcript(&replayed);
+    assert!(replayed.contains("recorded-row"), "{}", replayed);
+    assert_eq!(fake.requests("GetQueryExecution").len(), requests);
+    assert!(contents.contains("AmazonAthena.StartQueryExecution"));
+    assert!(!contents.contains("AKIDTEST"), "{}", contents);
+}
+
+#[test]
+fn highlights_the_position_of_a_syntax_error() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    fake.on(
+        "GetQueryExecution",
+        [query_execution(
+            "q-1",
+            "FAILED",
+            Some("line 3:21: mismatched input '='"),
+        )],
+    );
+    let script = std::env::temp_dir().join(format!("athena-shell-{}.sql", std::process::id()));
+    std::fs::write(&script, "SELECT id,\n  name\nFROM orders WHERE = 1;\n").unwrap();
+
+    let output = fake.run_shell(&["-f", script.to_str().unwrap()], "");
+    let _ = std::fs::remove_file(&script);
+
+    let transcript = transcript(&output);
+    let expected = "2 |   name\n3 | FROM orders WHERE = 1\n  |                     ^";
+    assert!(transcript.contains(expected), "{}", transcript);
+}
+
+#[test]
+fn highlights_the_position_of_a_syntax_error_typed_over_several_lines() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    fake.on(
+        "GetQueryExecution",
+        [query_execution(
+            "q-1",
+            "FAILED",
+            Some("line 3:21: mismatched input '='"),
+        )],
+    );
+
+    let output = fake.run_shell(&[], "SELECT id,\n  name\nFROM orders WHERE = 1;\n\\q\n");
+
+    let transcript = transcript(&output);
+    assert_eq!(
+        fake.requests("StartQueryExecution")[0].json()["QueryString"],
+        "SELECT id,\n  name\nFROM orders WHERE = 1"
+    );
+    let expected = "2 |   name\n3 | FROM orders WHERE = 1\n  |                     ^";
+    assert!(transcript.contains(expected), "{}", transcript);
+}
+
+#[test]
+fn logs_requests_and_statements_to_a_file() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    fake.on(
+        "GetQueryExecution",
+        [query_execution("q-1", "SUCCEEDED", None)],
+    );
+    fake.on("GetQueryResults", [result_page(&[&["id"], &["1"]], None)]);
+    let log = std::env::temp_dir().join(format!("athena-shell-{}.log", std::process::id()));
+    let log_path = log.to_str().unwrap();
+
+    let output = fake.run_shell(&["-v", "--log-file", log_path], "SELECT 1;\n\\q\n");
+
+    let contents = std::fs::read_to_string(&log).unwrap();
+    let _ = std::fs::remove_file(&log);
+    assert!(output.status.success(), "{}", transcript(&output));
+    assert!(
+        contents.contains("operation=\"StartQueryExecution\""),
+        "{}",
+        contents
+    );
+    assert!(contents.contains("latency_ms="), "{}", contents);
+    assert!(contents.contains("execution_id=\"q-1\""), "{}", contents);
+    assert!(contents.contains("statement succeeded"), "{}", contents);
+}
+
+#[test]
+fn resubmits_a_transiently_failed_script_statement() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1"), started("q-2")]);
+    fake.on(
+        "GetQueryExecution",
+        [
+            failed_transiently("q-1"),
+            query_execution("q-2", "SUCCEEDED", None),
+        ],
+    );
+    let script =
+        std::env::temp_dir().join(format!("athena-shell-{}-retry.sql", std::process::id()));
+    std::fs::write(&script, "SELECT 1;\n").unwrap();
+
+    let output = fake.run_shell(&["-f", script.to_str().unwrap()], "");
+    let _ = std::fs::remove_file(&script);
+
+    let transcript = transcript(&output);
+    assert!(output.status.success(), "{}", transcript);
+    assert!(
+        transcript.contains("[1] q-1 failed transiently"),
+        "{}",
+        transcript
+    );
+    assert!(transcript.contains("q-1, q-2"), "{}", transcript);
+    let tokens: Vec<_> = fake
+        .requests("StartQueryExecution")
+        .iter()
+        .map(|r| r.json()["ClientRequestToken"].clone())
+        .collect();
+    assert_ne!(tokens[0], tokens[1]);
+}
+
+#[test]
+fn does_not_resubmit_a_transiently_failed_insert() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    fake.on("GetQueryExecution", [failed_transiently("q-1")]);
+
+    let output = fake.run_shell(&["-e", "INSERT INTO orders SELECT * FROM staging"], "");
+
+    let transcript = transcript(&output);
+    assert!(!output.status.success(), "{}", transcript);
+    assert_eq!(fake.requests("StartQueryExecution").len(), 1);
+}
+
+#[test]
+fn gives_up_after_the_configured_query_retries() {
+    let fake = FakeAws::start();
+    fake.on("StartQueryExecution", [started("q-1")]);
+    fake.on("GetQueryExecution", [failed_transiently("q-1")]);
+
+    let output = fake.run_shell(&["--query-retries", "0", "-e", "SELECT 1"], "");
+
+    let transcript = transcript(&output);
+    assert!(!output.status.success(), "{}", transcript);
+    assert!(transcript.contains("S3 SlowDown"), "{}", transcript);
+    assert_eq!(fake.requests("StartQueryExecution").len(), 1);
+}
+
+#[test]
+fn lists_partitions_with_file_counts() {
+    let fake = FakeAws::start();
+    fake.on(
+        "GetTable",
+        [Reply::json(json!({
+            "Table": {
+                "Name": "orders",
+                "PartitionKeys": [{ "Name": "year" }, { "Name": "month" }]
+            }
+        }))],
+    );
+    let partition = |month: &str| {
+        json!({
+            "Values": ["2024", month],
+            "StorageDescriptor": { "Location": format!("s3://lake/orders/year=2024/month={}/", month) }
+        })
+    };
+    fake.on(
+        "GetPartitions",
+        [Reply::json(
+            json!({ "Partitions": [partition("1e0"), partition("10"), partition("9")] }),
+        )],
+    );
+    fake.on(
+        "ListObjectsV2",
+        [Reply::xml(
+            "<ListBucketResult><Name>lake</Name><IsTruncated>false</IsTruncated>\
+             <Contents><Key>orders/year=2024/month=9/</Key></Contents>\
+             <Contents><Key>orders/year=2024/month=9/a.parquet</Key></Contents>\
+             <Contents><Key>orders/year=2024/month=9/b.parquet</Key></Contents>\
+             </ListBucketResult>",
+        )],
+    );
+
+    let output = fake.run_shell(&[], "\\dp+ sales.orders year = '2024'\n\\q\n");
+
+    let transcript = transcript(&output);
+    let header = transcript.find("year | month").expect(&transcript);
+    // months sort numerically, not as text, and values other than integers after them
+    let september = transcript.find("month=9/").expect(&transcript);
+    let october = transcript.find("month=10/").expect(&transcript);
+    let other = transcript.find("month=1e0/").expect(&transcript);
+    assert!(header < september && september < october, "{}", transcript);
+    assert!(october < other, "{}", transcript);
+    assert!(transcript.contains("| 2 "), "{}", transcript);
+    assert!(transcript.contains("(3 rows)"), "{}", transcript);
+    assert_eq!(
+        fake.requests("GetPartitions")[0].json()["Expression"],
+        "year = '2024'"
+    );
+    // the partitions are counted concurrently, so in no particular order
+    let listings = fake.requests("ListObjectsV2");
+    assert_eq!(listings.len(), 3);
+    assert!(
+        listings.iter().any(|r| r
+            .target
+            .contains("prefix=orders%2Fyear%3D2024%2Fmonth%3D9%2F")),
+        "{:?}",
+        listings
+    );
+}
+
+// A Glue table with an id column and a customer_id column described by a comment
+fn orders_table() -> serde_json::Value {
+    json!({
+        "DatabaseName": "sales",
+        "Name": "orders",
+        "StorageDescriptor": {
+            "Columns": [
+                { "Name": "id", "Type": "bigint" },
+                { "Name": "buyer", "Type": "string", "Comment": "customer who placed the order" }
+            ]
+        },
+        "PartitionKeys": [{ "Name": "day", "Type": "string" }]
+    })
+}
+
+#[test]
+fn searches_tables_and_columns_with_glue() {
+    let fake = FakeAws::start();
+    fake.on(
+        "SearchTables",
+        [Reply::json(json!({ "TableList": [orders_table()] }))],
+    );
+
+    let output = fake.run_shell(&[], "\\search customer\n\\q\n");
+
+    let transcript = transcript(&output);
+    assert!(
+        transcript.contains(" sales    | orders | buyer  | string "),
+        "{}",
+        transcript
+    );
+    assert!(transcript.contains("(1 row)"), "{}", transcript);
+    assert_eq!(
+        fake.requests("SearchTables")[0].json()["SearchText"],
+        "customer"
+    );
+}
+
+#[test]
+fn searches_a_cached_catalog_without_search_permission() {
+    let fake = FakeAws::start();
+    fake.on(
+        "SearchTables",
+        [Reply::json_error(
+            400,
+            "AccessDeniedException",
+            "not authorized to perform glue:SearchTables",
+        )],
+    );
+    fake.on(
+        "GetDatabases",
+        [Reply::json(
+            json!({ "DatabaseList": [{ "Name": "sales" }] }),
+        )],
+    );
+    fake.on(
+        "GetTables",
+        [Reply::json(json!({ "TableList": [orders_table()] }))],
+    );
+
+    let output = fake.run_shell(&[], "\\search DAY\n\\search orders\n\\q\n");
+
+    let transcript = transcript(&output);
+    assert!(
+        transcript.contains("searching the cached catalog"),
+        "{}",
+        transcript
+    );
+    assert!(transcript.contains("| day    | string"), "{}", transcript);
+    assert!(
+        transcript.contains(" sales    | orders |        |"),
+        "{}",
+        transcript
+    );
+    // the second search reads the cache the first one crawled
+    assert_eq!(fake.requests("GetTables").len(), 1);
+}
+
+#[test]
+fn reports_search_failures_other_than_access_denied() {
+    let fake = FakeAws::start();
+    fake.on(
+        "SearchTables",
+        [Reply::json_error(
+            400,
+            "InvalidInputException",
+            "search text is too long",
+        )],
+    );
+
+    let output = fake.run_shell(&[], "\\search orders\n\\q\n");
+
+    let transcript = transcript(&output);
+    assert!(
+        transcript.contains("search text is too long"),
+        "{}",
+        transcript
+    );
+    assert!(!transcript.contains("cached catalog"), "{}", transcript);
+    assert!(fake.requests("GetDatabases").is_empty());
+}
+
+#[test]
+fn filters_listings_with_glob_patterns() {
+    let fake = FakeAws::start();
+    fake.on(
+        "GetTables",
+        [Reply::json(json!({
+            "TableList": [
+                { "Name": "sales_2023" },
+                { "Name": "sales_2024" },
+                { "Name": "returns" }
+            ]
+        }))],
+    );
+
+    let output = fake.run_shell(
+        &["--database", "lake"],
+        "\\lt sales_*\n\\lt lake *_202[!3]\n\\q\n",
+    );
+
+    let transcript = transcript(&output);
+    assert!(
+        transcript.contains(" sales_2023 \n sales_2024 \n(2 rows)"),
+        "{}",
+        transcript
+    );
+    assert!(
+        transcript.contains("-\n sales_2024 \n(1 row)"),
+        "{}",
+        transcript
+    );
+    assert!(!transcript.contains("returns"), "{}", transcript);
+    let requests = fake.requests("GetTables");
+    assert_eq!(requests[0].json()["DatabaseName"], "lake");
+    assert_eq!(requests[1].json()["DatabaseName"], "lake");
+}
+
+#[test]
+fn shows_table_details_from_glue() {
+    let fake = FakeAws::start();
+    let mut orders = orders_table();
+    orders["TableType"] = json!("EXTERNAL_TABLE");
+    orders["Owner"] = json!("etl");
+    orders["UpdateTime"] = json!(1720000000);
+    orders["StorageDescriptor"]["Location"] = json!("s3://lake/orders/");
+    fake.on(
+        "GetTables",
+        [Reply::json(
+            json!({ "TableList": [orders, { "Name": "customers" }] }),
+        )],
+    );
+
+    let output = fake.run_shell(&[], "\\lt+ sales ord*\n\\q\n");
+
+    let transcript = transcript(&output);
+    assert!(
+        transcript.contains(
+            "table  | type           | location          | owner | updated              | columns"
+        ),
+        "{}",
+        transcript
+    );
+    assert!(
+        transcript.contains(
+            "orders | EXTERNAL_TABLE | s3://lake/orders/ | etl   | 2024-07-03T09:46:40Z | 3"
+        ),
+        "{}",
+        transcript
+    );
+    assert!(!transcript.contains("customers"), "{}", transcript);
+    assert_eq!(
+        fake.requests("GetTables")[0].json()["DatabaseName"],
+        "sales"
+    );
+}